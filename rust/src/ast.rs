@@ -0,0 +1,161 @@
+use crate::expression::Expression;
+use crate::span::Span;
+use crate::statement::Statement;
+use std::marker::PhantomData;
+
+/// A stable handle to an `Expression` stored in an `Ast`
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+pub struct ExprId(u32);
+
+/// A stable handle to a `Statement` stored in an `Ast`
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+pub struct StmtId(u32);
+
+/// Implemented by the node handles so that side tables can be keyed off either of them
+pub trait NodeId: Copy {
+    fn index(self) -> usize;
+}
+
+impl NodeId for ExprId {
+    fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl NodeId for StmtId {
+    fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// Arena that owns every node of a program. Nodes refer to each other through `ExprId`/`StmtId`
+/// handles, which stay valid for as long as the arena lives. Every node is stored alongside the
+/// span of source it was parsed from.
+#[derive(Default)]
+pub struct Ast {
+    exprs: Vec<Expression>,
+    expr_spans: Vec<Span>,
+    stmts: Vec<Statement>,
+    stmt_spans: Vec<Span>,
+}
+
+impl Ast {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Moves an expression into the arena and returns its handle
+    pub fn alloc_expr(&mut self, expr: Expression, span: Span) -> ExprId {
+        let id = ExprId(self.exprs.len() as u32);
+        self.exprs.push(expr);
+        self.expr_spans.push(span);
+        id
+    }
+
+    /// Moves a statement into the arena and returns its handle
+    pub fn alloc_stmt(&mut self, stmt: Statement, span: Span) -> StmtId {
+        let id = StmtId(self.stmts.len() as u32);
+        self.stmts.push(stmt);
+        self.stmt_spans.push(span);
+        id
+    }
+
+    pub fn expr(&self, id: ExprId) -> &Expression {
+        &self.exprs[id.index()]
+    }
+
+    pub fn stmt(&self, id: StmtId) -> &Statement {
+        &self.stmts[id.index()]
+    }
+
+    /// The full extent of the expression `id`, including all of its children
+    pub fn expr_span(&self, id: ExprId) -> Span {
+        self.expr_spans[id.index()]
+    }
+
+    /// The full extent of the statement `id`
+    pub fn stmt_span(&self, id: StmtId) -> Span {
+        self.stmt_spans[id.index()]
+    }
+
+    /// Returns a value that renders the expression `id` in S-expression form
+    #[allow(dead_code)] // Only used by tests for now
+    pub fn display(&self, id: ExprId) -> ExprDisplay<'_> {
+        ExprDisplay { ast: self, id }
+    }
+}
+
+/// Borrowed view of an expression that implements `Display`, since nodes alone can't reach their
+/// children
+#[allow(dead_code)] // Only used by tests for now
+pub struct ExprDisplay<'a> {
+    pub ast: &'a Ast,
+    pub id: ExprId,
+}
+
+/// Per-node data attached by a pass (e.g. resolved scope depth) without touching the tree itself
+pub struct SideTable<I: NodeId, T> {
+    slots: Vec<Option<T>>,
+    _id: PhantomData<I>,
+}
+
+impl<I: NodeId, T> Default for SideTable<I, T> {
+    fn default() -> Self {
+        Self {
+            slots: vec![],
+            _id: PhantomData,
+        }
+    }
+}
+
+impl<I: NodeId, T> SideTable<I, T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, id: I, value: T) {
+        let idx = id.index();
+        if idx >= self.slots.len() {
+            self.slots.resize_with(idx + 1, || None);
+        }
+        self.slots[idx] = Some(value);
+    }
+
+    pub fn get(&self, id: I) -> Option<&T> {
+        self.slots.get(id.index()).and_then(|slot| slot.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expression::{Grouping, Literal};
+    use crate::number::Number;
+
+    #[test]
+    fn ids_are_stable() {
+        let mut ast = Ast::new();
+        let one = ast.alloc_expr(
+            Expression::Literal(Literal::Number(Number::Int(1))),
+            Span::new(1, 2, 1),
+        );
+        let group = ast.alloc_expr(Expression::Grouping(Grouping(one)), Span::new(0, 3, 1));
+
+        assert_eq!("1", format!("{}", ast.display(one)));
+        assert_eq!("(group 1)", format!("{}", ast.display(group)));
+        assert_eq!(Span::new(0, 3, 1), ast.expr_span(group));
+    }
+
+    #[test]
+    fn side_table() {
+        let mut ast = Ast::new();
+        let a = ast.alloc_expr(Expression::Literal(Literal::Nil), Span::default());
+        let b = ast.alloc_expr(Expression::Literal(Literal::Boolean(true)), Span::default());
+
+        let mut depths: SideTable<ExprId, usize> = SideTable::new();
+        depths.insert(b, 2);
+
+        assert_eq!(None, depths.get(a));
+        assert_eq!(Some(&2), depths.get(b));
+    }
+}
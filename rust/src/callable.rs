@@ -0,0 +1,177 @@
+use crate::ast::{Ast, StmtId};
+use crate::environment::Environment;
+use crate::error::RuntimeError;
+use crate::interpreter::{Interpreter, Unwind};
+use crate::scanner::Token;
+use crate::statement::Statement;
+use crate::value::Value;
+use std::{cell::RefCell, fmt::Debug, rc::Rc};
+
+/// Anything that can be called with `(...)`, whether it's written in Lox or in Rust
+pub trait Callable {
+    fn name(&self) -> &str;
+
+    /// The number of arguments the callable expects
+    fn arity(&self) -> usize;
+
+    /// Calls the callable with arguments that have already been checked against `arity`. `paren`
+    /// is the closing parenthesis of the call, which errors are reported at.
+    fn call(
+        self: Rc<Self>,
+        interpreter: &mut Interpreter,
+        ast: &Ast,
+        paren: &Token,
+        arguments: Vec<Value>,
+    ) -> Result<Value, RuntimeError>;
+}
+
+impl Debug for dyn Callable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<fn {}>", self.name())
+    }
+}
+
+/// A function declared in Lox, along with the environment it closes over
+pub struct LoxFunction {
+    name: String,
+    arity: usize,
+    declaration: StmtId,
+    closure: Rc<RefCell<Environment>>,
+    /// Whether this is a class's `init` method, which always returns `this`
+    is_initializer: bool,
+    /// Whether this is a method that's called as soon as it's accessed
+    is_getter: bool,
+}
+
+impl LoxFunction {
+    /// Creates a function from `declaration`, which must be a `Statement::Function`
+    pub fn new(
+        ast: &Ast,
+        declaration: StmtId,
+        closure: Rc<RefCell<Environment>>,
+        is_initializer: bool,
+    ) -> Self {
+        let Statement::Function(function) = ast.stmt(declaration) else {
+            panic!("functions can only be created from function declarations");
+        };
+
+        Self {
+            name: function.name.lexeme.to_owned(),
+            arity: function.params.len(),
+            declaration,
+            closure,
+            is_initializer,
+            is_getter: function.getter,
+        }
+    }
+
+    /// Returns a copy of this method whose `this` refers to `instance`
+    pub fn bind(&self, instance: Value) -> LoxFunction {
+        let mut environment = Environment::with_enclosing(Rc::clone(&self.closure));
+        environment.define("this", instance);
+
+        LoxFunction {
+            name: self.name.to_owned(),
+            arity: self.arity,
+            declaration: self.declaration,
+            closure: Rc::new(RefCell::new(environment)),
+            is_initializer: self.is_initializer,
+            is_getter: self.is_getter,
+        }
+    }
+
+    pub fn is_getter(&self) -> bool {
+        self.is_getter
+    }
+
+    /// The instance a bound method's `this` refers to
+    fn this(&self) -> Value {
+        self.closure
+            .borrow()
+            .get_local("this")
+            .expect("initializers are only called once bound")
+    }
+}
+
+impl Callable for LoxFunction {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn arity(&self) -> usize {
+        self.arity
+    }
+
+    fn call(
+        self: Rc<Self>,
+        interpreter: &mut Interpreter,
+        ast: &Ast,
+        _paren: &Token,
+        arguments: Vec<Value>,
+    ) -> Result<Value, RuntimeError> {
+        let Statement::Function(function) = ast.stmt(self.declaration) else {
+            unreachable!("checked in LoxFunction::new");
+        };
+
+        let mut environment = Environment::with_enclosing(Rc::clone(&self.closure));
+        for (param, argument) in function.params.iter().zip(arguments) {
+            environment.define(&param.lexeme, argument);
+        }
+
+        match interpreter.execute_block(ast, &function.body, environment) {
+            // Initializers return `this`, even from an early `return;`
+            Ok(()) | Err(Unwind::Return(_)) if self.is_initializer => Ok(self.this()),
+            Ok(()) => Ok(Value::Nil),
+            Err(Unwind::Return(value)) => Ok(value),
+            Err(Unwind::Error(err)) => Err(err),
+            // The parser rejects `break` and `continue` that aren't inside a loop in the function
+            Err(Unwind::Break | Unwind::Continue) => {
+                unreachable!("loop control escaped a function")
+            }
+        }
+    }
+}
+
+/// The signature of a function implemented in Rust. Errors are reported at the call site.
+pub type NativeFn = dyn Fn(&mut Interpreter, Vec<Value>) -> Result<Value, String>;
+
+/// A function implemented in Rust
+pub struct NativeFunction {
+    name: String,
+    arity: usize,
+    function: Box<NativeFn>,
+}
+
+impl NativeFunction {
+    pub fn new(
+        name: &str,
+        arity: usize,
+        function: impl Fn(&mut Interpreter, Vec<Value>) -> Result<Value, String> + 'static,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            arity,
+            function: Box::new(function),
+        }
+    }
+}
+
+impl Callable for NativeFunction {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn arity(&self) -> usize {
+        self.arity
+    }
+
+    fn call(
+        self: Rc<Self>,
+        interpreter: &mut Interpreter,
+        _ast: &Ast,
+        paren: &Token,
+        arguments: Vec<Value>,
+    ) -> Result<Value, RuntimeError> {
+        (self.function)(interpreter, arguments).map_err(|msg| RuntimeError::new(paren, msg))
+    }
+}
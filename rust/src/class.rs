@@ -0,0 +1,174 @@
+use crate::ast::Ast;
+use crate::callable::{Callable, LoxFunction};
+use crate::error::RuntimeError;
+use crate::interpreter::Interpreter;
+use crate::scanner::Token;
+use crate::value::Value;
+use std::{cell::RefCell, collections::HashMap, fmt::Debug, rc::Rc};
+
+/// A class declared in Lox. Calling it creates an instance.
+pub struct LoxClass {
+    pub name: String,
+    superclass: Option<Rc<LoxClass>>,
+    methods: HashMap<String, Rc<LoxFunction>>,
+    /// The class of the class, holding its static methods. Metaclasses don't have one themselves.
+    metaclass: Option<Rc<LoxClass>>,
+}
+
+impl LoxClass {
+    /// Creates a class along with a metaclass holding `class_methods`. The metaclass inherits
+    /// from the superclass's metaclass, so static methods are inherited like any other.
+    pub fn new(
+        name: &str,
+        superclass: Option<Rc<LoxClass>>,
+        methods: HashMap<String, Rc<LoxFunction>>,
+        class_methods: HashMap<String, Rc<LoxFunction>>,
+    ) -> Self {
+        let metaclass = LoxClass {
+            name: format!("{name} metaclass"),
+            superclass: superclass
+                .as_ref()
+                .and_then(|superclass| superclass.metaclass.clone()),
+            methods: class_methods,
+            metaclass: None,
+        };
+
+        Self {
+            name: name.to_string(),
+            superclass,
+            methods,
+            metaclass: Some(Rc::new(metaclass)),
+        }
+    }
+
+    /// Looks `name` up in this class and then each of its superclasses
+    pub fn find_method(&self, name: &str) -> Option<Rc<LoxFunction>> {
+        match self.methods.get(name) {
+            Some(method) => Some(Rc::clone(method)),
+            None => self.superclass.as_ref()?.find_method(name),
+        }
+    }
+
+    /// Looks up the static method `name`, which is inherited like any other method
+    pub fn find_class_method(&self, name: &str) -> Option<Rc<LoxFunction>> {
+        self.metaclass.as_ref()?.find_method(name)
+    }
+}
+
+impl Callable for LoxClass {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Takes whatever the initializer does
+    fn arity(&self) -> usize {
+        self.find_method("init").map_or(0, |init| init.arity())
+    }
+
+    fn call(
+        self: Rc<Self>,
+        interpreter: &mut Interpreter,
+        ast: &Ast,
+        paren: &Token,
+        arguments: Vec<Value>,
+    ) -> Result<Value, RuntimeError> {
+        let instance = Value::Instance(Rc::new(RefCell::new(LoxInstance::new(Rc::clone(&self)))));
+
+        if let Some(init) = self.find_method("init") {
+            Rc::new(init.bind(instance.clone())).call(interpreter, ast, paren, arguments)?;
+        }
+
+        Ok(instance)
+    }
+}
+
+impl Debug for LoxClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<class {}>", self.name)
+    }
+}
+
+/// A named set of methods that classes mix in with `with`
+pub struct LoxTrait {
+    pub name: String,
+    pub methods: HashMap<String, Rc<LoxFunction>>,
+}
+
+impl Debug for LoxTrait {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<trait {}>", self.name)
+    }
+}
+
+/// An instance of a `LoxClass`, holding its own fields
+pub struct LoxInstance {
+    pub class: Rc<LoxClass>,
+    fields: HashMap<String, Value>,
+}
+
+impl LoxInstance {
+    pub fn new(class: Rc<LoxClass>) -> Self {
+        Self {
+            class,
+            fields: HashMap::new(),
+        }
+    }
+
+    pub fn set(&mut self, name: &Token, value: Value) {
+        self.fields.insert(name.lexeme.to_owned(), value);
+    }
+}
+
+impl Debug for LoxInstance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<{} instance>", self.class.name)
+    }
+}
+
+/// Looks up the property `name` on `object`. On an instance, fields shadow methods; on a class,
+/// the properties are its static methods. Methods come back bound to `object`, and getters are
+/// run straight away.
+pub fn get(
+    interpreter: &mut Interpreter,
+    ast: &Ast,
+    object: Value,
+    name: &Token,
+) -> Result<Value, RuntimeError> {
+    let method = match &object {
+        Value::Instance(instance) => {
+            if let Some(value) = instance.borrow().fields.get(&name.lexeme) {
+                return Ok(value.clone());
+            }
+            instance.borrow().class.find_method(&name.lexeme)
+        }
+        Value::Class(class) => class.find_class_method(&name.lexeme),
+        _ => {
+            return Err(RuntimeError::new(name, "Only instances have properties."));
+        }
+    };
+
+    match method {
+        Some(method) => bind(interpreter, ast, &method, object, name),
+        None => Err(RuntimeError::new(
+            name,
+            format!("Undefined property '{}'.", name.lexeme),
+        )),
+    }
+}
+
+/// Binds `method` to `object`, running it straight away if it's a getter. `name` is where the
+/// method was accessed, which errors in a getter are reported at.
+pub fn bind(
+    interpreter: &mut Interpreter,
+    ast: &Ast,
+    method: &LoxFunction,
+    object: Value,
+    name: &Token,
+) -> Result<Value, RuntimeError> {
+    let bound = Rc::new(method.bind(object));
+    if bound.is_getter() {
+        bound.call(interpreter, ast, name, vec![])
+    } else {
+        Ok(Value::Callable(bound))
+    }
+}
@@ -0,0 +1,112 @@
+use crate::error::RuntimeError;
+use crate::scanner::Token;
+use crate::value::Value;
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+/// The variables of one scope, chained to the scope that encloses it
+#[derive(Default)]
+pub struct Environment {
+    values: HashMap<String, Value>,
+    enclosing: Option<Rc<RefCell<Environment>>>,
+}
+
+impl Environment {
+    /// Creates the global scope
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a scope nested inside `enclosing`
+    pub fn with_enclosing(enclosing: Rc<RefCell<Environment>>) -> Self {
+        Self {
+            values: HashMap::new(),
+            enclosing: Some(enclosing),
+        }
+    }
+
+    /// Binds `name` in this scope, replacing any existing binding
+    pub fn define(&mut self, name: &str, value: Value) {
+        self.values.insert(name.to_string(), value);
+    }
+
+    /// Looks `name` up in this scope only
+    pub fn get_local(&self, name: &str) -> Option<Value> {
+        self.values.get(name).cloned()
+    }
+
+    /// Looks `name` up in this scope and then each enclosing one
+    pub fn get(&self, name: &Token) -> Result<Value, RuntimeError> {
+        if let Some(value) = self.values.get(&name.lexeme) {
+            return Ok(value.clone());
+        }
+
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow().get(name),
+            None => Err(undefined(name)),
+        }
+    }
+
+    /// Reassigns the nearest existing binding of `name`
+    pub fn assign(&mut self, name: &Token, value: Value) -> Result<(), RuntimeError> {
+        if let Some(slot) = self.values.get_mut(&name.lexeme) {
+            *slot = value;
+            return Ok(());
+        }
+
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow_mut().assign(name, value),
+            None => Err(undefined(name)),
+        }
+    }
+}
+
+/// Walks `distance` scopes out from `environment`
+pub fn ancestor(
+    environment: &Rc<RefCell<Environment>>,
+    distance: usize,
+) -> Rc<RefCell<Environment>> {
+    let mut environment = Rc::clone(environment);
+    for _ in 0..distance {
+        let enclosing = environment
+            .borrow()
+            .enclosing
+            .clone()
+            .expect("the resolver only records distances to scopes that exist");
+        environment = enclosing;
+    }
+    environment
+}
+
+/// Looks `name` up in the scope exactly `distance` scopes out from `environment`
+pub fn get_at(
+    environment: &Rc<RefCell<Environment>>,
+    distance: usize,
+    name: &Token,
+) -> Result<Value, RuntimeError> {
+    ancestor(environment, distance)
+        .borrow()
+        .values
+        .get(&name.lexeme)
+        .cloned()
+        .ok_or_else(|| undefined(name))
+}
+
+/// Reassigns `name` in the scope exactly `distance` scopes out from `environment`
+pub fn assign_at(
+    environment: &Rc<RefCell<Environment>>,
+    distance: usize,
+    name: &Token,
+    value: Value,
+) -> Result<(), RuntimeError> {
+    let ancestor = ancestor(environment, distance);
+    let mut ancestor = ancestor.borrow_mut();
+    let Some(slot) = ancestor.values.get_mut(&name.lexeme) else {
+        return Err(undefined(name));
+    };
+    *slot = value;
+    Ok(())
+}
+
+fn undefined(name: &Token) -> RuntimeError {
+    RuntimeError::new(name, format!("Undefined variable '{}'.", name.lexeme))
+}
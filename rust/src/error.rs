@@ -1,3 +1,6 @@
+use crate::scanner::{Token, TokenType};
+use std::fmt::Display;
+
 pub fn lox_error(line: usize, msg: String) {
     report(line, "".to_string(), msg);
 }
@@ -5,3 +8,71 @@ pub fn lox_error(line: usize, msg: String) {
 pub fn report(line: usize, location: String, msg: String) {
     eprintln!("[line {line}] Error({location}): {msg}");
 }
+
+/// Reports an error found while parsing `token`
+pub fn token_error(token: &Token, msg: &str) {
+    if token.token_type == TokenType::EOF {
+        report(token.line, "at end".to_string(), msg.to_string());
+    } else {
+        report(
+            token.line,
+            format!("at '{}'", token.lexeme),
+            msg.to_string(),
+        );
+    }
+}
+
+/// The ways running a piece of source can fail. Both have already been reported by the time
+/// they're returned.
+#[derive(Debug, PartialEq)]
+pub enum LoxError {
+    /// The source couldn't be parsed
+    Static,
+    /// The source failed while it was being run
+    Runtime,
+}
+
+impl LoxError {
+    /// The code to exit with, following the conventions in sysexits.h
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            LoxError::Static => 65,
+            LoxError::Runtime => 70,
+        }
+    }
+}
+
+/// An error raised while running a program, pointing at the token that caused it
+#[derive(Debug)]
+pub struct RuntimeError {
+    // Boxed to keep the `Err` side of evaluation results small
+    pub token: Box<Token>,
+    pub message: String,
+    /// Calls that were active when the error was raised, innermost first
+    pub trace: Vec<String>,
+}
+
+impl RuntimeError {
+    pub fn new(token: &Token, message: impl Into<String>) -> Self {
+        Self {
+            token: Box::new(token.clone()),
+            message: message.into(),
+            trace: vec![],
+        }
+    }
+}
+
+impl Display for RuntimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}\n[line {}]", self.message, self.token.line)?;
+        for frame in self.trace.iter() {
+            write!(f, "\n{frame}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Reports an error raised while running a program
+pub fn runtime_error(err: &RuntimeError) {
+    eprintln!("{err}");
+}
@@ -1,3 +1,5 @@
+use crate::ast::{ExprDisplay, ExprId};
+use crate::number::Number;
 use crate::scanner::Token;
 use std::fmt::Display;
 
@@ -6,29 +8,194 @@ pub enum Expression {
     Unary(Unary),
     Binary(Binary),
     Grouping(Grouping),
+    Conditional(Conditional),
+    Comma(Comma),
+    Variable(Variable),
+    Assign(Assign),
+    Logical(Logical),
+    Call(Call),
+    Get(Get),
+    Set(Set),
+    This(This),
+    Super(Super),
+    List(List),
+    Map(Map),
+    Index(Index),
+    SetIndex(SetIndex),
+    Update(Update),
 }
 
-impl Display for Expression {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl Expression {
+    /// Handles of the direct children of this node
+    pub fn children(&self) -> Vec<ExprId> {
         match self {
-            Expression::Binary(expr) => write!(f, "{expr}"),
-            Expression::Literal(expr) => write!(f, "{expr}"),
-            Expression::Unary(expr) => write!(f, "{expr}"),
-            Expression::Grouping(expr) => write!(f, "{expr}"),
+            Expression::Literal(_) => vec![],
+            Expression::Unary(expr) => vec![expr.right],
+            Expression::Binary(expr) => vec![expr.left, expr.right],
+            Expression::Grouping(expr) => vec![expr.0],
+            Expression::Conditional(expr) => {
+                vec![expr.condition, expr.then_branch, expr.else_branch]
+            }
+            Expression::Comma(expr) => vec![expr.left, expr.right],
+            Expression::Variable(_) => vec![],
+            Expression::Assign(expr) => vec![expr.value],
+            Expression::Logical(expr) => vec![expr.left, expr.right],
+            Expression::Call(expr) => {
+                let mut children = vec![expr.callee];
+                children.extend(expr.arguments.iter());
+                children
+            }
+            Expression::Get(expr) => vec![expr.object],
+            Expression::Set(expr) => vec![expr.object, expr.value],
+            Expression::This(_) | Expression::Super(_) => vec![],
+            Expression::List(expr) => expr.elements.clone(),
+            Expression::Map(expr) => expr
+                .entries
+                .iter()
+                .flat_map(|(key, value)| [*key, *value])
+                .collect(),
+            Expression::Index(expr) => {
+                let mut children = vec![expr.object];
+                match expr.subscript {
+                    Subscript::Index(index) => children.push(index),
+                    Subscript::Slice(start, end) => children.extend(start.into_iter().chain(end)),
+                }
+                children
+            }
+            Expression::SetIndex(expr) => vec![expr.object, expr.index, expr.value],
+            Expression::Update(expr) => {
+                let mut children = vec![expr.target];
+                children.extend(expr.value);
+                children
+            }
         }
     }
 }
 
-pub struct Grouping(Box<Expression>);
-
-impl Display for Grouping {
+impl Display for ExprDisplay<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "(group {})", self.0)
+        let ast = self.ast;
+        match ast.expr(self.id) {
+            Expression::Binary(expr) => write!(
+                f,
+                "({} {} {})",
+                expr.operator.lexeme,
+                ast.display(expr.left),
+                ast.display(expr.right)
+            ),
+            Expression::Literal(expr) => write!(f, "{expr}"),
+            Expression::Unary(expr) => {
+                write!(f, "({} {})", expr.operator.lexeme, ast.display(expr.right))
+            }
+            Expression::Grouping(expr) => write!(f, "(group {})", ast.display(expr.0)),
+            Expression::Conditional(expr) => write!(
+                f,
+                "(?: {} {} {})",
+                ast.display(expr.condition),
+                ast.display(expr.then_branch),
+                ast.display(expr.else_branch)
+            ),
+            Expression::Comma(expr) => write!(
+                f,
+                "(, {} {})",
+                ast.display(expr.left),
+                ast.display(expr.right)
+            ),
+            Expression::Variable(expr) => write!(f, "{}", expr.name.lexeme),
+            Expression::Assign(expr) => {
+                write!(f, "(= {} {})", expr.name.lexeme, ast.display(expr.value))
+            }
+            Expression::Logical(expr) => write!(
+                f,
+                "({} {} {})",
+                expr.operator.lexeme,
+                ast.display(expr.left),
+                ast.display(expr.right)
+            ),
+            Expression::Call(expr) => {
+                write!(f, "(call {}", ast.display(expr.callee))?;
+                for argument in expr.arguments.iter() {
+                    write!(f, " {}", ast.display(*argument))?;
+                }
+                write!(f, ")")
+            }
+            Expression::Get(expr) => {
+                write!(f, "(. {} {})", ast.display(expr.object), expr.name.lexeme)
+            }
+            Expression::Set(expr) => write!(
+                f,
+                "(= (. {} {}) {})",
+                ast.display(expr.object),
+                expr.name.lexeme,
+                ast.display(expr.value)
+            ),
+            Expression::This(_) => write!(f, "this"),
+            Expression::Super(expr) => write!(f, "(super {})", expr.method.lexeme),
+            Expression::List(expr) => {
+                write!(f, "(list")?;
+                for element in expr.elements.iter() {
+                    write!(f, " {}", ast.display(*element))?;
+                }
+                write!(f, ")")
+            }
+            Expression::Map(expr) => {
+                write!(f, "(map")?;
+                for (key, value) in expr.entries.iter() {
+                    write!(f, " {} {}", ast.display(*key), ast.display(*value))?;
+                }
+                write!(f, ")")
+            }
+            Expression::Index(expr) => match expr.subscript {
+                Subscript::Index(index) => {
+                    write!(
+                        f,
+                        "([] {} {})",
+                        ast.display(expr.object),
+                        ast.display(index)
+                    )
+                }
+                Subscript::Slice(start, end) => {
+                    // Missing bounds are shown as `_`
+                    write!(f, "([:] {}", ast.display(expr.object))?;
+                    for bound in [start, end] {
+                        match bound {
+                            Some(bound) => write!(f, " {}", ast.display(bound))?,
+                            None => write!(f, " _")?,
+                        }
+                    }
+                    write!(f, ")")
+                }
+            },
+            Expression::SetIndex(expr) => write!(
+                f,
+                "(= ([] {} {}) {})",
+                ast.display(expr.object),
+                ast.display(expr.index),
+                ast.display(expr.value)
+            ),
+            Expression::Update(expr) => match (expr.value, expr.postfix) {
+                (Some(value), _) => write!(
+                    f,
+                    "({} {} {})",
+                    expr.operator.lexeme,
+                    ast.display(expr.target),
+                    ast.display(value)
+                ),
+                (None, false) => {
+                    write!(f, "({} {})", expr.operator.lexeme, ast.display(expr.target))
+                }
+                (None, true) => {
+                    write!(f, "({} {})", ast.display(expr.target), expr.operator.lexeme)
+                }
+            },
+        }
     }
 }
 
+pub struct Grouping(pub ExprId);
+
 pub enum Literal {
-    Number(f64),
+    Number(Number),
     String(String),
     Boolean(bool),
     Nil,
@@ -46,72 +213,204 @@ impl Display for Literal {
 }
 
 pub struct Unary {
-    operator: Token,
-    right: Box<Expression>,
+    pub operator: Token,
+    pub right: ExprId,
 }
 
-impl Display for Unary {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "({} {})", self.operator.lexeme, self.right,)
-    }
+pub struct Binary {
+    pub left: ExprId,
+    pub operator: Token,
+    pub right: ExprId,
 }
 
-pub struct Binary {
-    left: Box<Expression>,
-    operator: Token,
-    right: Box<Expression>,
+/// `condition ? then_branch : else_branch`
+pub struct Conditional {
+    pub condition: ExprId,
+    pub then_branch: ExprId,
+    pub else_branch: ExprId,
 }
 
-impl Display for Binary {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "({} {} {})", self.operator.lexeme, self.left, self.right)
-    }
+/// `left, right`: evaluates both operands and produces the right one
+pub struct Comma {
+    pub left: ExprId,
+    pub right: ExprId,
+}
+
+/// A reference to a variable by name
+pub struct Variable {
+    pub name: Token,
+}
+
+/// `name = value`
+pub struct Assign {
+    pub name: Token,
+    pub value: ExprId,
+}
+
+/// `left and right` or `left or right`, which only evaluates `right` when it has to
+pub struct Logical {
+    pub left: ExprId,
+    pub operator: Token,
+    pub right: ExprId,
+}
+
+/// `callee(arguments)`
+pub struct Call {
+    pub callee: ExprId,
+    /// The closing parenthesis, which runtime errors for the call are reported at
+    pub paren: Token,
+    pub arguments: Vec<ExprId>,
+}
+
+/// `object.name`
+pub struct Get {
+    pub object: ExprId,
+    pub name: Token,
+}
+
+/// `object.name = value`
+pub struct Set {
+    pub object: ExprId,
+    pub name: Token,
+    pub value: ExprId,
+}
+
+/// `this` inside a method
+pub struct This {
+    pub keyword: Token,
+}
+
+/// `super.method` inside a method of a subclass
+pub struct Super {
+    pub keyword: Token,
+    pub method: Token,
+}
+
+/// `[elements]`
+pub struct List {
+    pub elements: Vec<ExprId>,
+}
+
+/// `{key: value, ...}`
+pub struct Map {
+    /// The `{`, which errors in the keys are reported at
+    pub brace: Token,
+    pub entries: Vec<(ExprId, ExprId)>,
+}
+
+/// `object[subscript]`
+pub struct Index {
+    pub object: ExprId,
+    /// The `[`, spanning the whole subscript so that errors point at it
+    pub bracket: Token,
+    pub subscript: Subscript,
+}
+
+/// What goes between the brackets of an `Index`
+#[derive(Clone, Copy)]
+pub enum Subscript {
+    Index(ExprId),
+    /// `start:end`, either of which may be left out
+    Slice(Option<ExprId>, Option<ExprId>),
+}
+
+/// `object[index] = value`
+pub struct SetIndex {
+    pub object: ExprId,
+    /// The `[`, spanning the whole subscript so that errors point at it
+    pub bracket: Token,
+    pub index: ExprId,
+    pub value: ExprId,
+}
+
+/// `target op= value`, `++target` or `target++`, and likewise for `--`. The target is a
+/// `Variable`, `Get` or `Index` whose operands are evaluated once, for both reading and writing.
+pub struct Update {
+    pub target: ExprId,
+    pub operator: Token,
+    /// The right-hand side of a compound assignment, or `None` for `++` and `--`
+    pub value: Option<ExprId>,
+    /// Whether `++` or `--` came after the target, making the old value the result
+    pub postfix: bool,
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ast::Ast;
     use crate::scanner::{TokenLiteral, TokenType};
+    use crate::span::Span;
 
     #[test]
     fn display_simple() {
-        let expr = Expression::Binary(Binary {
-            left: Box::new(Expression::Literal(Literal::Number(1 as f64))),
-            operator: Token {
-                token_type: TokenType::Plus,
-                lexeme: "+".to_string(),
-                literal: TokenLiteral::None,
-                line: 1,
-            },
-            right: Box::new(Expression::Literal(Literal::Number(2 as f64))),
-        });
+        let mut ast = Ast::new();
+        let left = ast.alloc_expr(
+            Expression::Literal(Literal::Number(Number::Int(1))),
+            Span::new(0, 1, 1),
+        );
+        let right = ast.alloc_expr(
+            Expression::Literal(Literal::Number(Number::Int(2))),
+            Span::new(4, 5, 1),
+        );
+        let expr = ast.alloc_expr(
+            Expression::Binary(Binary {
+                left,
+                operator: Token::new(
+                    TokenType::Plus,
+                    "+".to_string(),
+                    TokenLiteral::None,
+                    Span::new(2, 3, 1),
+                ),
+                right,
+            }),
+            Span::new(0, 5, 1),
+        );
 
-        assert_eq!("(+ 1 2)", format!("{expr}").as_str())
+        assert_eq!("(+ 1 2)", format!("{}", ast.display(expr)).as_str())
     }
 
     #[test]
     fn display_complex() {
-        let expr = Expression::Binary(Binary {
-            left: Box::new(Expression::Unary(Unary {
-                operator: Token {
-                    token_type: TokenType::Minus,
-                    lexeme: "-".to_string(),
-                    literal: TokenLiteral::None,
-                    line: 1,
-                },
-                right: Box::new(Expression::Literal(Literal::Number(123 as f64))),
-            })),
-            operator: Token {
-                token_type: TokenType::Star,
-                lexeme: "*".to_string(),
-                literal: TokenLiteral::None,
-                line: 1,
-            },
-            right: Box::new(Expression::Grouping(Grouping(Box::new(
-                Expression::Literal(Literal::Number(45.67)),
-            )))),
-        });
+        let mut ast = Ast::new();
+        let number = ast.alloc_expr(
+            Expression::Literal(Literal::Number(Number::Int(123))),
+            Span::new(1, 4, 1),
+        );
+        let left = ast.alloc_expr(
+            Expression::Unary(Unary {
+                operator: Token::new(
+                    TokenType::Minus,
+                    "-".to_string(),
+                    TokenLiteral::None,
+                    Span::new(0, 1, 1),
+                ),
+                right: number,
+            }),
+            Span::new(0, 4, 1),
+        );
+        let grouped = ast.alloc_expr(
+            Expression::Literal(Literal::Number(Number::Float(45.67))),
+            Span::new(8, 13, 1),
+        );
+        let right = ast.alloc_expr(Expression::Grouping(Grouping(grouped)), Span::new(7, 14, 1));
+        let expr = ast.alloc_expr(
+            Expression::Binary(Binary {
+                left,
+                operator: Token::new(
+                    TokenType::Star,
+                    "*".to_string(),
+                    TokenLiteral::None,
+                    Span::new(5, 6, 1),
+                ),
+                right,
+            }),
+            Span::new(0, 14, 1),
+        );
 
-        assert_eq!("(* (- 123) (group 45.67))", format!("{expr}").as_str())
+        assert_eq!(
+            "(* (- 123) (group 45.67))",
+            format!("{}", ast.display(expr)).as_str()
+        );
+        assert_eq!(vec![left, right], ast.expr(expr).children());
     }
 }
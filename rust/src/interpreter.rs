@@ -0,0 +1,1703 @@
+use crate::ast::{Ast, ExprId, SideTable, StmtId};
+use crate::callable::{Callable, LoxFunction};
+use crate::class::{self, LoxClass, LoxTrait};
+use crate::environment::{self, Environment};
+use crate::error::RuntimeError;
+use crate::expression::{Binary, Call, Expression, Index, SetIndex, Subscript, Unary, Update};
+use crate::iterator::LoxIterator;
+use crate::list;
+use crate::map::{self, Map};
+use crate::native;
+use crate::number::Number;
+use crate::range::{self, Range};
+use crate::scanner::{Token, TokenType};
+use crate::statement::{Class, ForIn, Statement, Trait};
+use crate::value::Value;
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    io::{self, BufRead, BufReader, Write},
+    rc::Rc,
+};
+
+/// Why executing a statement stopped before reaching its end
+pub enum Unwind {
+    Error(RuntimeError),
+    Return(Value),
+    Break,
+    Continue,
+}
+
+impl From<RuntimeError> for Unwind {
+    fn from(err: RuntimeError) -> Self {
+        Unwind::Error(err)
+    }
+}
+
+/// What dividing by zero does, whether with `/`, `~/` or `%`
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum Division {
+    /// Follows IEEE 754, giving an infinity or NaN
+    #[default]
+    Ieee,
+    /// Fails with a runtime error
+    Strict,
+}
+
+/// Tree-walking interpreter that runs nodes stored in an `Ast`
+pub struct Interpreter {
+    globals: Rc<RefCell<Environment>>,
+    environment: Rc<RefCell<Environment>>,
+    /// How many scopes out each local variable reference was declared, filled in by the
+    /// resolver. References that aren't in here are globals.
+    locals: SideTable<ExprId, usize>,
+    input: Box<dyn BufRead>,
+    output: Box<dyn Write>,
+    division: Division,
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        Self::with_io(
+            Box::new(BufReader::new(io::stdin())),
+            Box::new(io::stdout()),
+        )
+    }
+
+    /// Creates an interpreter that reads `input()` from `input` and sends the output of `print`
+    /// to `output`
+    pub fn with_io(input: Box<dyn BufRead>, output: Box<dyn Write>) -> Self {
+        let mut globals = Environment::new();
+        native::install(&mut globals);
+
+        let globals = Rc::new(RefCell::new(globals));
+        Self {
+            environment: Rc::clone(&globals),
+            globals,
+            locals: SideTable::new(),
+            input,
+            output,
+            division: Division::default(),
+        }
+    }
+
+    pub fn set_division(&mut self, division: Division) {
+        self.division = division;
+    }
+
+    /// Records that the variable referenced by `id` was declared `depth` scopes out
+    pub fn resolve(&mut self, id: ExprId, depth: usize) {
+        self.locals.insert(id, depth);
+    }
+
+    /// Reads a line of input without its line ending, or `None` once the input is exhausted
+    pub fn read_line(&mut self) -> io::Result<Option<String>> {
+        let mut line = String::new();
+        if self.input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+
+        let trimmed = line.trim_end_matches(['\n', '\r']).len();
+        line.truncate(trimmed);
+        Ok(Some(line))
+    }
+
+    /// Runs each of `statements` in turn, stopping at the first error
+    pub fn interpret(&mut self, ast: &Ast, statements: &[StmtId]) -> Result<(), RuntimeError> {
+        match self.execute_all(ast, statements) {
+            Ok(()) => Ok(()),
+            Err(Unwind::Error(err)) => Err(err),
+            // The resolver rejects `return` outside of functions
+            Err(Unwind::Return(_)) => unreachable!("return escaped a function"),
+            // The parser rejects `break` and `continue` outside of loops
+            Err(Unwind::Break | Unwind::Continue) => unreachable!("loop control escaped a loop"),
+        }
+    }
+
+    fn execute_all(&mut self, ast: &Ast, statements: &[StmtId]) -> Result<(), Unwind> {
+        for stmt in statements {
+            self.execute(ast, *stmt)?;
+        }
+        Ok(())
+    }
+
+    fn execute(&mut self, ast: &Ast, id: StmtId) -> Result<(), Unwind> {
+        match ast.stmt(id) {
+            Statement::Expression(expr) => {
+                self.evaluate(ast, *expr)?;
+            }
+            Statement::Print(stmt) => {
+                let value = self.evaluate(ast, stmt.value)?;
+                writeln!(self.output, "{value}")
+                    .map_err(|e| RuntimeError::new(&stmt.keyword, format!("Can't print: {e}.")))?;
+            }
+            Statement::Var(var) => {
+                let value = match var.initializer {
+                    Some(initializer) => self.evaluate(ast, initializer)?,
+                    None => Value::Nil,
+                };
+                self.environment
+                    .borrow_mut()
+                    .define(&var.name.lexeme, value);
+            }
+            Statement::Block(statements) => {
+                let environment = Environment::with_enclosing(Rc::clone(&self.environment));
+                self.execute_block(ast, statements, environment)?;
+            }
+            Statement::If(stmt) => {
+                if self.evaluate(ast, stmt.condition)?.is_truthy() {
+                    self.execute(ast, stmt.then_branch)?;
+                } else if let Some(else_branch) = stmt.else_branch {
+                    self.execute(ast, else_branch)?;
+                }
+            }
+            Statement::While(stmt) => {
+                while self.evaluate(ast, stmt.condition)?.is_truthy() {
+                    match self.execute(ast, stmt.body) {
+                        Ok(()) | Err(Unwind::Continue) => (),
+                        Err(Unwind::Break) => break,
+                        Err(e) => return Err(e),
+                    }
+
+                    // Runs after `continue` too, which is why it isn't just part of the body
+                    if let Some(increment) = stmt.increment {
+                        self.evaluate(ast, increment)?;
+                    }
+                }
+            }
+            Statement::ForIn(stmt) => self.for_in(ast, stmt)?,
+            Statement::Function(function) => {
+                let closure = Rc::clone(&self.environment);
+                let value = Value::Callable(Rc::new(LoxFunction::new(ast, id, closure, false)));
+                self.environment
+                    .borrow_mut()
+                    .define(&function.name.lexeme, value);
+            }
+            Statement::Return(stmt) => {
+                let value = match stmt.value {
+                    Some(value) => self.evaluate(ast, value)?,
+                    None => Value::Nil,
+                };
+                return Err(Unwind::Return(value));
+            }
+            Statement::Class(class) => self.class_declaration(ast, class)?,
+            Statement::Trait(stmt) => self.trait_declaration(ast, stmt),
+            Statement::Break => return Err(Unwind::Break),
+            Statement::Continue => return Err(Unwind::Continue),
+        }
+
+        Ok(())
+    }
+
+    fn for_in(&mut self, ast: &Ast, stmt: &ForIn) -> Result<(), Unwind> {
+        let iterable = self.evaluate(ast, stmt.iterable)?;
+        let mut iterator = LoxIterator::new(self, ast, iterable, &stmt.keyword)?;
+
+        while let Some(value) = iterator.next(self, ast, &stmt.keyword)? {
+            // Each iteration gets its own variable, so closures made in the body keep its value
+            let mut environment = Environment::with_enclosing(Rc::clone(&self.environment));
+            environment.define(&stmt.name.lexeme, value);
+            match self.execute_block(ast, std::slice::from_ref(&stmt.body), environment) {
+                Ok(()) | Err(Unwind::Continue) => (),
+                Err(Unwind::Break) => break,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Creates the class declared by `class` and binds it in the current environment
+    fn class_declaration(&mut self, ast: &Ast, class: &Class) -> Result<(), RuntimeError> {
+        let superclass = match class.superclass {
+            Some(superclass) => match self.evaluate(ast, superclass)? {
+                Value::Class(superclass) => Some(superclass),
+                _ => {
+                    return Err(RuntimeError::new(
+                        variable_name(ast, superclass),
+                        "Superclass must be a class.",
+                    ));
+                }
+            },
+            None => None,
+        };
+
+        // Methods of a subclass close over an extra scope binding `super`
+        let enclosing = Rc::clone(&self.environment);
+        if let Some(superclass) = &superclass {
+            let mut environment = Environment::with_enclosing(Rc::clone(&enclosing));
+            environment.define("super", Value::Class(Rc::clone(superclass)));
+            self.environment = Rc::new(RefCell::new(environment));
+        }
+
+        let mut methods = self.methods(ast, &class.methods, true);
+        let mixed = self.mix_in(ast, &class.traits, &mut methods);
+        // Static methods named `init` aren't initializers
+        let class_methods = self.methods(ast, &class.class_methods, false);
+        // Leave the `super` scope even when mixing in failed, so later code runs where it should
+        self.environment = enclosing;
+        mixed?;
+
+        let class_value = LoxClass::new(&class.name.lexeme, superclass, methods, class_methods);
+        let value = Value::Class(Rc::new(class_value));
+        self.environment
+            .borrow_mut()
+            .define(&class.name.lexeme, value);
+        Ok(())
+    }
+
+    /// Creates the trait declared by `stmt` and binds it in the current environment
+    fn trait_declaration(&mut self, ast: &Ast, stmt: &Trait) {
+        let methods = self.methods(ast, &stmt.methods, true);
+        let value = Value::Trait(Rc::new(LoxTrait {
+            name: stmt.name.lexeme.to_owned(),
+            methods,
+        }));
+        self.environment
+            .borrow_mut()
+            .define(&stmt.name.lexeme, value);
+    }
+
+    /// Adds the methods of each of `traits` to a class's own `methods`. A class's own methods
+    /// win over its traits', which in turn win over its superclass's. Two traits providing the
+    /// same method is an error unless the class overrides it, since neither is more specific.
+    fn mix_in(
+        &mut self,
+        ast: &Ast,
+        traits: &[ExprId],
+        methods: &mut HashMap<String, Rc<LoxFunction>>,
+    ) -> Result<(), RuntimeError> {
+        let mut mixed_in: HashMap<String, Rc<LoxTrait>> = HashMap::new();
+        for expr in traits {
+            let name = variable_name(ast, *expr);
+            let Value::Trait(lox_trait) = self.evaluate(ast, *expr)? else {
+                return Err(RuntimeError::new(name, "Can only mix in traits."));
+            };
+
+            for (method_name, method) in lox_trait.methods.iter() {
+                if methods.contains_key(method_name) && !mixed_in.contains_key(method_name) {
+                    continue;
+                }
+                if let Some(other) = mixed_in.get(method_name) {
+                    return Err(RuntimeError::new(
+                        name,
+                        format!(
+                            "Traits '{}' and '{}' both define '{method_name}'.",
+                            other.name, lox_trait.name
+                        ),
+                    ));
+                }
+                mixed_in.insert(method_name.to_owned(), Rc::clone(&lox_trait));
+                methods.insert(method_name.to_owned(), Rc::clone(method));
+            }
+        }
+        Ok(())
+    }
+
+    /// Creates the methods declared by `declarations`, closing over the current environment
+    fn methods(
+        &self,
+        ast: &Ast,
+        declarations: &[StmtId],
+        has_initializer: bool,
+    ) -> HashMap<String, Rc<LoxFunction>> {
+        let mut methods = HashMap::new();
+        for method in declarations {
+            let Statement::Function(function) = ast.stmt(*method) else {
+                unreachable!("the parser only puts functions in class bodies");
+            };
+            let name = function.name.lexeme.to_owned();
+            let is_initializer = has_initializer && name == "init";
+            let closure = Rc::clone(&self.environment);
+            let method = LoxFunction::new(ast, *method, closure, is_initializer);
+            methods.insert(name, Rc::new(method));
+        }
+        methods
+    }
+
+    /// Runs `statements` in `environment`, restoring the current environment afterwards even if
+    /// one of them fails
+    pub fn execute_block(
+        &mut self,
+        ast: &Ast,
+        statements: &[StmtId],
+        environment: Environment,
+    ) -> Result<(), Unwind> {
+        let previous = std::mem::replace(&mut self.environment, Rc::new(RefCell::new(environment)));
+        let result = self.execute_all(ast, statements);
+        self.environment = previous;
+        result
+    }
+
+    /// Evaluates the expression `id` to a value
+    pub fn evaluate(&mut self, ast: &Ast, id: ExprId) -> Result<Value, RuntimeError> {
+        match ast.expr(id) {
+            Expression::Literal(literal) => Ok(Value::from(literal)),
+            Expression::Grouping(grouping) => self.evaluate(ast, grouping.0),
+            Expression::Unary(unary) => self.unary(ast, unary),
+            Expression::Binary(binary) => self.binary(ast, binary),
+            Expression::Conditional(conditional) => {
+                // Only the chosen branch is evaluated
+                if self.evaluate(ast, conditional.condition)?.is_truthy() {
+                    self.evaluate(ast, conditional.then_branch)
+                } else {
+                    self.evaluate(ast, conditional.else_branch)
+                }
+            }
+            Expression::Comma(comma) => {
+                self.evaluate(ast, comma.left)?;
+                self.evaluate(ast, comma.right)
+            }
+            Expression::Variable(variable) => self.look_up_variable(id, &variable.name),
+            Expression::Logical(logical) => {
+                let left = self.evaluate(ast, logical.left)?;
+                // The operand that decides the result is returned as-is rather than as a boolean
+                let decided = match logical.operator.token_type {
+                    TokenType::Or => left.is_truthy(),
+                    _ => !left.is_truthy(),
+                };
+                if decided {
+                    Ok(left)
+                } else {
+                    self.evaluate(ast, logical.right)
+                }
+            }
+            Expression::Call(call) => self.call(ast, call),
+            Expression::Get(get) => match self.evaluate(ast, get.object)? {
+                Value::List(list) => list::method(&list, &get.name),
+                Value::Map(map) => map::method(&map, &get.name),
+                Value::Range(range) => range::method(range, &get.name),
+                object => class::get(self, ast, object, &get.name),
+            },
+            Expression::Set(set) => {
+                let Value::Instance(instance) = self.evaluate(ast, set.object)? else {
+                    return Err(RuntimeError::new(&set.name, "Only instances have fields."));
+                };
+                let value = self.evaluate(ast, set.value)?;
+                instance.borrow_mut().set(&set.name, value.clone());
+                Ok(value)
+            }
+            Expression::This(this) => self.look_up_variable(id, &this.keyword),
+            Expression::List(list) => {
+                let mut elements = Vec::with_capacity(list.elements.len());
+                for element in list.elements.iter() {
+                    elements.push(self.evaluate(ast, *element)?);
+                }
+                Ok(Value::List(Rc::new(RefCell::new(elements))))
+            }
+            Expression::Map(literal) => {
+                let mut map = Map::new();
+                for (key, value) in literal.entries.iter() {
+                    let key_value = self.evaluate(ast, *key)?;
+                    let value = self.evaluate(ast, *value)?;
+                    map.insert(key_value, value)
+                        .map_err(|msg| RuntimeError::new(&literal.brace, msg))?;
+                }
+                Ok(Value::Map(Rc::new(RefCell::new(map))))
+            }
+            Expression::Index(index) => self.index(ast, index),
+            Expression::SetIndex(set) => self.set_index(ast, set),
+            Expression::Super(expr) => {
+                let distance = *self
+                    .locals
+                    .get(id)
+                    .expect("the resolver always resolves 'super'");
+                let Value::Class(superclass) =
+                    environment::get_at(&self.environment, distance, &expr.keyword)?
+                else {
+                    unreachable!("'super' is always bound to a class");
+                };
+                // `this` is bound in the scope just inside the one binding `super`
+                let this = environment::ancestor(&self.environment, distance - 1)
+                    .borrow()
+                    .get_local("this")
+                    .expect("methods always have 'this' bound");
+
+                // In a static method `this` is the class, so `super` looks for static methods
+                let method = match this {
+                    Value::Class(_) => superclass.find_class_method(&expr.method.lexeme),
+                    _ => superclass.find_method(&expr.method.lexeme),
+                };
+                match method {
+                    Some(method) => class::bind(self, ast, &method, this, &expr.method),
+                    None => Err(RuntimeError::new(
+                        &expr.method,
+                        format!("Undefined property '{}'.", expr.method.lexeme),
+                    )),
+                }
+            }
+            Expression::Assign(assign) => {
+                let value = self.evaluate(ast, assign.value)?;
+                self.assign_variable(id, &assign.name, value.clone())?;
+                Ok(value)
+            }
+            Expression::Update(update) => self.update(ast, update),
+        }
+    }
+
+    /// Assigns to the variable referenced by `id`
+    fn assign_variable(&self, id: ExprId, name: &Token, value: Value) -> Result<(), RuntimeError> {
+        match self.locals.get(id) {
+            Some(distance) => environment::assign_at(&self.environment, *distance, name, value),
+            None => self.globals.borrow_mut().assign(name, value),
+        }
+    }
+
+    fn look_up_variable(&self, id: ExprId, name: &Token) -> Result<Value, RuntimeError> {
+        match self.locals.get(id) {
+            Some(distance) => environment::get_at(&self.environment, *distance, name),
+            None => self.globals.borrow().get(name),
+        }
+    }
+
+    fn call(&mut self, ast: &Ast, call: &Call) -> Result<Value, RuntimeError> {
+        let callee = self.evaluate(ast, call.callee)?;
+
+        let mut arguments = Vec::with_capacity(call.arguments.len());
+        for argument in call.arguments.iter() {
+            arguments.push(self.evaluate(ast, *argument)?);
+        }
+
+        self.call_value(ast, callee, arguments, &call.paren)
+    }
+
+    /// Calls `callee` with `arguments`, reporting errors at `paren`
+    pub fn call_value(
+        &mut self,
+        ast: &Ast,
+        callee: Value,
+        arguments: Vec<Value>,
+        paren: &Token,
+    ) -> Result<Value, RuntimeError> {
+        let callable: Rc<dyn Callable> = match callee {
+            Value::Callable(callable) => callable,
+            Value::Class(class) => class,
+            _ => {
+                return Err(RuntimeError::new(
+                    paren,
+                    "Can only call functions and classes.",
+                ));
+            }
+        };
+
+        if arguments.len() != callable.arity() {
+            return Err(RuntimeError::new(
+                paren,
+                format!(
+                    "Expected {} arguments but got {}.",
+                    callable.arity(),
+                    arguments.len()
+                ),
+            ));
+        }
+
+        Rc::clone(&callable)
+            .call(self, ast, paren, arguments)
+            .map_err(|mut err| {
+                err.trace.push(format!(
+                    "in {}(), called at [line {}]",
+                    callable.name(),
+                    paren.line
+                ));
+                err
+            })
+    }
+
+    fn index(&mut self, ast: &Ast, index: &Index) -> Result<Value, RuntimeError> {
+        let object = self.evaluate(ast, index.object)?;
+        let error = |msg| RuntimeError::new(&index.bracket, msg);
+
+        match index.subscript {
+            Subscript::Index(position) => {
+                let position = self.evaluate(ast, position)?;
+                get_index(&object, &position).map_err(error)
+            }
+            Subscript::Slice(start, end) => {
+                let start = start.map(|start| self.evaluate(ast, start)).transpose()?;
+                let end = end.map(|end| self.evaluate(ast, end)).transpose()?;
+                let Value::List(list) = object else {
+                    return Err(error("Only lists can be sliced.".to_string()));
+                };
+                let list = list.borrow();
+                let range = list::slice(list.len(), start.as_ref(), end.as_ref()).map_err(error)?;
+                Ok(Value::List(Rc::new(RefCell::new(list[range].to_vec()))))
+            }
+        }
+    }
+
+    fn set_index(&mut self, ast: &Ast, set: &SetIndex) -> Result<Value, RuntimeError> {
+        let object = self.evaluate(ast, set.object)?;
+        let position = self.evaluate(ast, set.index)?;
+        let value = self.evaluate(ast, set.value)?;
+        set_index(&object, position, value.clone())
+            .map_err(|msg| RuntimeError::new(&set.bracket, msg))?;
+        Ok(value)
+    }
+
+    /// Compound assignment, `++` and `--`. The target's operands are evaluated once, before the
+    /// right-hand side, and the same object is both read and written.
+    fn update(&mut self, ast: &Ast, update: &Update) -> Result<Value, RuntimeError> {
+        match ast.expr(update.target) {
+            Expression::Variable(variable) => {
+                let old = self.look_up_variable(update.target, &variable.name)?;
+                let (new, result) = self.updated(ast, update, old)?;
+                self.assign_variable(update.target, &variable.name, new)?;
+                Ok(result)
+            }
+            Expression::Get(get) => {
+                let object = self.evaluate(ast, get.object)?;
+                let Value::Instance(instance) = &object else {
+                    return Err(RuntimeError::new(&get.name, "Only instances have fields."));
+                };
+                let old = class::get(self, ast, object.clone(), &get.name)?;
+                let (new, result) = self.updated(ast, update, old)?;
+                instance.borrow_mut().set(&get.name, new);
+                Ok(result)
+            }
+            Expression::Index(Index {
+                object,
+                bracket,
+                subscript: Subscript::Index(position),
+            }) => {
+                let error = |msg| RuntimeError::new(bracket, msg);
+                let object = self.evaluate(ast, *object)?;
+                let position = self.evaluate(ast, *position)?;
+                let old = get_index(&object, &position).map_err(error)?;
+                let (new, result) = self.updated(ast, update, old)?;
+                set_index(&object, position, new).map_err(error)?;
+                Ok(result)
+            }
+            _ => unreachable!("the parser only updates variables, properties and elements"),
+        }
+    }
+
+    /// The new value for a target of `update` that holds `old`, along with what the whole
+    /// expression evaluates to: the old value for postfix `++` and `--`, otherwise the new one
+    fn updated(
+        &mut self,
+        ast: &Ast,
+        update: &Update,
+        old: Value,
+    ) -> Result<(Value, Value), RuntimeError> {
+        let operator = &update.operator;
+        let new = match update.value {
+            Some(value) => {
+                let right = self.evaluate(ast, value)?;
+                let token_type = match operator.token_type {
+                    TokenType::PlusEqual => TokenType::Plus,
+                    TokenType::MinusEqual => TokenType::Minus,
+                    TokenType::StarEqual => TokenType::Star,
+                    TokenType::SlashEqual => TokenType::Slash,
+                    TokenType::PercentEqual => TokenType::Percent,
+                    ref token_type => unreachable!("{token_type} is not a compound assignment"),
+                };
+                self.arithmetic(operator, &token_type, old.clone(), right)?
+            }
+            None => {
+                let Value::Number(n) = &old else {
+                    return Err(RuntimeError::new(operator, "Operand must be a number."));
+                };
+                let one = Number::Int(1);
+                Value::Number(match operator.token_type {
+                    TokenType::PlusPlus => n.add(&one),
+                    _ => n.subtract(&one),
+                })
+            }
+        };
+
+        if update.postfix {
+            Ok((new, old))
+        } else {
+            Ok((new.clone(), new))
+        }
+    }
+
+    fn unary(&mut self, ast: &Ast, unary: &Unary) -> Result<Value, RuntimeError> {
+        let right = self.evaluate(ast, unary.right)?;
+
+        match (&unary.operator.token_type, right) {
+            (TokenType::Bang, right) => Ok(Value::Boolean(!right.is_truthy())),
+            (TokenType::Minus, Value::Number(n)) => Ok(Value::Number(n.negate())),
+            (TokenType::Minus, _) => Err(RuntimeError::new(
+                &unary.operator,
+                "Operand must be a number.",
+            )),
+            (token_type, _) => unreachable!("{token_type} is not a unary operator"),
+        }
+    }
+
+    /// Whether `collection` contains `value`, for the `in` operator
+    fn contains(
+        &self,
+        collection: &Value,
+        value: &Value,
+        operator: &Token,
+    ) -> Result<Value, RuntimeError> {
+        let contains = match (collection, value) {
+            (Value::List(list), value) => list.borrow().contains(value),
+            // Values that can't be keys can't be in the map either
+            (Value::Map(map), key) => matches!(map.borrow().get(key), Ok(Some(_))),
+            (Value::Range(range), value) => range.contains(value),
+            (Value::String(s), Value::String(part)) => s.contains(part.as_str()),
+            (Value::String(_), _) => {
+                return Err(RuntimeError::new(
+                    operator,
+                    "Only strings can be in a string.",
+                ));
+            }
+            _ => {
+                return Err(RuntimeError::new(
+                    operator,
+                    "Can only look in lists, maps, ranges and strings.",
+                ));
+            }
+        };
+        Ok(Value::Boolean(contains))
+    }
+
+    fn binary(&mut self, ast: &Ast, binary: &Binary) -> Result<Value, RuntimeError> {
+        let left = self.evaluate(ast, binary.left)?;
+        let right = self.evaluate(ast, binary.right)?;
+
+        match &binary.operator.token_type {
+            TokenType::EqualEqual => Ok(Value::Boolean(left == right)),
+            TokenType::BangEqual => Ok(Value::Boolean(left != right)),
+            TokenType::DotDot | TokenType::DotDotEqual => {
+                let inclusive = binary.operator.token_type == TokenType::DotDotEqual;
+                Range::new(&left, &right, inclusive)
+                    .map(Value::Range)
+                    .map_err(|msg| RuntimeError::new(&binary.operator, msg))
+            }
+            TokenType::In => self.contains(&right, &left, &binary.operator),
+            token_type => self.arithmetic(&binary.operator, token_type, left, right),
+        }
+    }
+
+    /// Applies the arithmetic or comparison operator `token_type` to two operands. `operator` is
+    /// where errors are reported, which differs from `token_type` for compound assignments.
+    fn arithmetic(
+        &self,
+        operator: &Token,
+        token_type: &TokenType,
+        left: Value,
+        right: Value,
+    ) -> Result<Value, RuntimeError> {
+        if *token_type == TokenType::Plus {
+            return match (left, right) {
+                (Value::Number(l), Value::Number(r)) => Ok(Value::Number(l.add(&r))),
+                (Value::String(l), Value::String(r)) => Ok(Value::String(l + &r)),
+                _ => Err(RuntimeError::new(
+                    operator,
+                    "Operands must be two numbers or two strings.",
+                )),
+            };
+        }
+
+        let (Value::Number(l), Value::Number(r)) = (left, right) else {
+            return Err(RuntimeError::new(operator, "Operands must be numbers."));
+        };
+
+        let is_division = matches!(
+            token_type,
+            TokenType::Slash | TokenType::TildeSlash | TokenType::Percent
+        );
+        if is_division && r.is_zero() && self.division == Division::Strict {
+            return Err(RuntimeError::new(operator, "Division by zero."));
+        }
+
+        let result = match token_type {
+            TokenType::Minus => Ok(l.subtract(&r)),
+            TokenType::Star => Ok(l.multiply(&r)),
+            TokenType::Slash => Ok(l.divide(&r)),
+            TokenType::TildeSlash => l.floor_divide(&r),
+            TokenType::Percent => l.modulo(&r),
+            TokenType::StarStar => l.power(&r),
+            TokenType::Greater => return Ok(Value::Boolean(l > r)),
+            TokenType::GreaterEqual => return Ok(Value::Boolean(l >= r)),
+            TokenType::Less => return Ok(Value::Boolean(l < r)),
+            TokenType::LessEqual => return Ok(Value::Boolean(l <= r)),
+            _ => unreachable!("{token_type} is not a binary operator"),
+        };
+        result
+            .map(Value::Number)
+            .map_err(|msg| RuntimeError::new(operator, msg))
+    }
+}
+
+/// The element of a list, map or range at `position`
+fn get_index(object: &Value, position: &Value) -> Result<Value, String> {
+    match object {
+        Value::List(list) => {
+            let list = list.borrow();
+            let position = list::index(list.len(), position, "list")?;
+            Ok(list[position].clone())
+        }
+        Value::Map(map) => match map.borrow().get(position)? {
+            Some(value) => Ok(value.clone()),
+            None => Err(map::missing_key(position)),
+        },
+        Value::Range(range) => range.get(position),
+        _ => Err("Only lists, maps and ranges can be indexed.".to_string()),
+    }
+}
+
+/// Sets the element of a list or map at `position`
+fn set_index(object: &Value, position: Value, value: Value) -> Result<(), String> {
+    match object {
+        Value::List(list) => {
+            let mut list = list.borrow_mut();
+            let position = list::index(list.len(), &position, "list")?;
+            list[position] = value;
+            Ok(())
+        }
+        Value::Map(map) => map.borrow_mut().insert(position, value),
+        _ => Err("Only lists and maps can be indexed.".to_string()),
+    }
+}
+
+/// The name in an `Expression::Variable`, which is all the parser allows as a superclass or trait
+fn variable_name(ast: &Ast, id: ExprId) -> &Token {
+    match ast.expr(id) {
+        Expression::Variable(variable) => &variable.name,
+        _ => unreachable!("the parser only allows names as superclasses and traits"),
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::number::Number;
+    use crate::parser::Parser;
+    use crate::resolver::Resolver;
+    use crate::scanner::Scanner;
+
+    /// Collects everything printed so the tests can inspect it
+    #[derive(Clone, Default)]
+    pub struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+    impl SharedBuffer {
+        pub fn contents(&self) -> String {
+            String::from_utf8(self.0.borrow().clone()).unwrap()
+        }
+    }
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn evaluate(source: &str) -> Result<Value, RuntimeError> {
+        let tokens = Scanner::new(source.to_string()).scan_tokens().unwrap();
+        let mut ast = Ast::new();
+        let expr = Parser::new(tokens, &mut ast).parse_expression().unwrap();
+        Interpreter::new().evaluate(&ast, expr)
+    }
+
+    /// Runs a program, returning what it printed and the error it stopped at, if any
+    pub fn run(source: &str) -> (String, Option<RuntimeError>) {
+        run_with_input(source, "")
+    }
+
+    /// Like `run`, with `input` available to read
+    pub fn run_with_input(source: &str, input: &str) -> (String, Option<RuntimeError>) {
+        let tokens = Scanner::new(source.to_string()).scan_tokens().unwrap();
+        let mut ast = Ast::new();
+        let statements = Parser::new(tokens, &mut ast).parse().unwrap();
+
+        let output = SharedBuffer::default();
+        let mut interpreter = Interpreter::with_io(
+            Box::new(io::Cursor::new(input.to_string())),
+            Box::new(output.clone()),
+        );
+        Resolver::new(&ast, &mut interpreter)
+            .resolve(&statements)
+            .unwrap();
+        let result = interpreter.interpret(&ast, &statements);
+
+        (output.contents(), result.err())
+    }
+
+    #[test]
+    fn print_errors() {
+        /// Output that has gone away, like a pipe whose reader has exited
+        struct ClosedPipe;
+
+        impl Write for ClosedPipe {
+            fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+                Err(io::ErrorKind::BrokenPipe.into())
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let tokens = Scanner::new("print 1;".to_string()).scan_tokens().unwrap();
+        let mut ast = Ast::new();
+        let statements = Parser::new(tokens, &mut ast).parse().unwrap();
+        let mut interpreter = Interpreter::with_io(
+            Box::new(io::Cursor::new(String::new())),
+            Box::new(ClosedPipe),
+        );
+
+        let err = interpreter.interpret(&ast, &statements).unwrap_err();
+        assert_eq!("Can't print: broken pipe.", err.message);
+        assert_eq!(TokenType::Print, err.token.token_type);
+    }
+
+    #[test]
+    fn arithmetic() {
+        assert_eq!("7", evaluate("1 + 2 * 3").unwrap().to_string());
+        assert_eq!("-1.5", evaluate("-(6 / 4)").unwrap().to_string());
+        assert_eq!("3", evaluate("10 - 7").unwrap().to_string());
+    }
+
+    #[test]
+    fn arithmetic_operators() {
+        let cases = [
+            ("7 % 3", "1"),
+            ("-7 % 3", "2"),
+            ("7 % -3", "-2"),
+            ("2.5 % 1", "0.5"),
+            ("7 ~/ 2", "3"),
+            ("-7 ~/ 2", "-4"),
+            ("7.5 ~/ 2", "3.0"),
+            ("2 ** 3 ** 2", "512"),
+            ("-2 ** 2", "-4"),
+            ("2 ** -1", "0.5"),
+        ];
+        for (source, result) in cases {
+            assert_eq!(result, evaluate(source).unwrap().to_string(), "{source}");
+        }
+    }
+
+    #[test]
+    fn integers_and_floats() {
+        let cases = [
+            ("1 + 2", "3"),
+            ("1 + 2.0", "3.0"),
+            ("1.5 * 2", "3.0"),
+            ("4 / 2", "2.0"),
+            ("9007199254740993 + 0", "9007199254740993"),
+            ("1 == 1.0", "true"),
+            ("9007199254740993 > 9007199254740992.0", "true"),
+        ];
+        for (source, result) in cases {
+            assert_eq!(result, evaluate(source).unwrap().to_string(), "{source}");
+        }
+    }
+
+    #[test]
+    fn big_integers() {
+        let cases = [
+            ("9223372036854775807 + 1", "9223372036854775808"),
+            ("-9223372036854775807 - 2", "-9223372036854775809"),
+            ("4294967296 * 4294967296", "18446744073709551616"),
+            ("-(-9223372036854775807 - 1)", "9223372036854775808"),
+            ("3 ** 40", "12157665459056928801"),
+            ("100000000000000000000 ~/ 3", "33333333333333333333"),
+            ("-100000000000000000000 % 7", "5"),
+            ("100000000000000000000 - 99999999999999999999", "1"),
+            ("100000000000000000000 / 8", "1.25e19"),
+            ("100000000000000000000 + 0.5", "1e20"),
+            ("100000000000000000000 == 100000000000000000000.0", "true"),
+            ("100000000000000000001 > 100000000000000000000.0", "true"),
+            ("18446744073709551616 == 2 ** 64", "true"),
+        ];
+        for (source, result) in cases {
+            assert_eq!(result, evaluate(source).unwrap().to_string(), "{source}");
+        }
+
+        let err = evaluate("2 ** 5000000000").unwrap_err();
+        assert_eq!("Exponent is too large.", err.message);
+    }
+
+    #[test]
+    fn division_by_zero() {
+        let evaluate_with = |source: &str, division| {
+            let tokens = Scanner::new(source.to_string()).scan_tokens().unwrap();
+            let mut ast = Ast::new();
+            let expr = Parser::new(tokens, &mut ast).parse_expression().unwrap();
+            let mut interpreter = Interpreter::new();
+            interpreter.set_division(division);
+            interpreter.evaluate(&ast, expr)
+        };
+
+        let ieee = |source| evaluate_with(source, Division::Ieee).map(|value| value.to_string());
+        assert_eq!("inf", ieee("1 / 0").unwrap());
+        assert_eq!("-inf", ieee("-1.0 ~/ 0").unwrap());
+        assert_eq!("NaN", ieee("1.0 % 0").unwrap());
+        // Integers have no infinity, so these fail either way
+        assert_eq!("Division by zero.", ieee("1 ~/ 0").unwrap_err().message);
+        assert_eq!("Division by zero.", ieee("1 % 0").unwrap_err().message);
+
+        for source in ["1 / 0", "1.0 ~/ 0", "1 % 0.0"] {
+            let err = evaluate_with(source, Division::Strict).unwrap_err();
+            assert_eq!("Division by zero.", err.message);
+        }
+        assert_eq!(
+            "0.0",
+            evaluate_with("0 / 1", Division::Strict)
+                .unwrap()
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn comparison_and_equality() {
+        assert_eq!(Value::Boolean(true), evaluate("1 < 2 == 3 >= 3").unwrap());
+        assert_eq!(Value::Boolean(true), evaluate("nil == nil").unwrap());
+        assert_eq!(Value::Boolean(false), evaluate("nil == false").unwrap());
+        assert_eq!(Value::Boolean(true), evaluate("\"a\" != 1").unwrap());
+    }
+
+    #[test]
+    fn strings() {
+        assert_eq!(
+            Value::String("foobar".to_string()),
+            evaluate("\"foo\" + \"bar\"").unwrap()
+        );
+    }
+
+    #[test]
+    fn truthiness() {
+        assert_eq!(Value::Boolean(false), evaluate("!0").unwrap());
+        assert_eq!(Value::Boolean(true), evaluate("!nil").unwrap());
+    }
+
+    #[test]
+    fn conditional_and_comma() {
+        assert_eq!(
+            Value::Number(Number::Int(1)),
+            evaluate("0 ? 1 : 2").unwrap()
+        );
+        assert_eq!(
+            Value::Number(Number::Int(2)),
+            evaluate("nil ? 1 : 2").unwrap()
+        );
+        // The branch that isn't taken would fail if it were evaluated
+        assert_eq!(
+            Value::Number(Number::Int(1)),
+            evaluate("true ? 1 : -\"x\"").unwrap()
+        );
+        assert_eq!(Value::Number(Number::Int(3)), evaluate("1, 2, 3").unwrap());
+    }
+
+    #[test]
+    fn logical() {
+        assert_eq!(
+            Value::String("hi".to_string()),
+            evaluate("\"hi\" or 2").unwrap()
+        );
+        assert_eq!(Value::Number(Number::Int(2)), evaluate("nil or 2").unwrap());
+        assert_eq!(Value::Nil, evaluate("nil and 2").unwrap());
+        assert_eq!(Value::Number(Number::Int(2)), evaluate("1 and 2").unwrap());
+        // The right operand would fail if it were evaluated
+        assert_eq!(Value::Boolean(true), evaluate("true or -\"x\"").unwrap());
+        assert_eq!(Value::Boolean(false), evaluate("false and -\"x\"").unwrap());
+    }
+
+    #[test]
+    fn type_errors() {
+        let err = evaluate("\"a\" - 1").unwrap_err();
+        assert_eq!("Operands must be numbers.", err.message);
+        assert_eq!(TokenType::Minus, err.token.token_type);
+
+        let err = evaluate("-\"x\"").unwrap_err();
+        assert_eq!("Operand must be a number.", err.message);
+
+        let err = evaluate("1 +\n nil").unwrap_err();
+        assert_eq!("Operands must be two numbers or two strings.", err.message);
+        assert_eq!(
+            "Operands must be two numbers or two strings.\n[line 1]",
+            err.to_string()
+        );
+    }
+
+    #[test]
+    fn variables() {
+        let (output, err) = run("var a = 1; var b; print a; print b; a = b = 3; print a + b;");
+        assert_eq!("1\nnil\n6\n", output);
+        assert!(err.is_none());
+    }
+
+    #[test]
+    fn updates() {
+        let source = "
+            var a = 1;
+            a += 2;
+            print a;
+            print a++;
+            print a;
+            print ++a;
+            print a--;
+            print --a;
+            var s = \"a\";
+            s += \"b\";
+            print s;
+            {
+                var local = 1;
+                local *= 5;
+                local %= 3;
+                print local;
+            }
+            var m = {\"k\": 1};
+            m[\"k\"] /= 2;
+            print m[\"k\"];
+        ";
+        let (output, err) = run(source);
+        assert_eq!("3\n3\n4\n5\n5\n3\nab\n2\n0.5\n", output);
+        assert!(err.is_none());
+    }
+
+    #[test]
+    fn updates_evaluate_targets_once() {
+        let source = "
+            class Box {}
+            var box = Box();
+            box.n = 10;
+            var calls = 0;
+            fun get() {
+                calls++;
+                return box;
+            }
+            fun zero() {
+                calls++;
+                return 0;
+            }
+            get().n *= 2;
+            print get().n++;
+            print box.n;
+            var xs = [1, 2];
+            xs[zero()] += 10;
+            print --xs[zero()];
+            print xs;
+            print calls;
+        ";
+        let (output, err) = run(source);
+        assert_eq!("20\n21\n10\n[10, 2]\n4\n", output);
+        assert!(err.is_none());
+    }
+
+    #[test]
+    fn update_errors() {
+        let (_, err) = run("var s = \"a\"; s++;");
+        let err = err.unwrap();
+        assert_eq!("Operand must be a number.", err.message);
+        assert_eq!(TokenType::PlusPlus, err.token.token_type);
+
+        let (_, err) = run("var s = \"a\"; s -= 1;");
+        let err = err.unwrap();
+        assert_eq!("Operands must be numbers.", err.message);
+        assert_eq!(TokenType::MinusEqual, err.token.token_type);
+
+        let (_, err) = run("var m = {}; m[\"x\"] += 1;");
+        assert_eq!("Key 'x' isn't in the map.", err.unwrap().message);
+
+        let (_, err) = run("class C {} C().n += 1;");
+        assert_eq!("Undefined property 'n'.", err.unwrap().message);
+
+        let (_, err) = run("var xs = [1]; xs.len += 1;");
+        assert_eq!("Only instances have fields.", err.unwrap().message);
+
+        let (_, err) = run("undefined++;");
+        assert_eq!("Undefined variable 'undefined'.", err.unwrap().message);
+    }
+
+    #[test]
+    fn block_scopes() {
+        let source = "
+            var a = \"global a\";
+            var b = \"global b\";
+            {
+                var a = \"outer a\";
+                {
+                    var a = \"inner a\";
+                    b = \"assigned b\";
+                    print a;
+                }
+                print a;
+            }
+            print a;
+            print b;
+        ";
+        let (output, err) = run(source);
+        assert_eq!("inner a\nouter a\nglobal a\nassigned b\n", output);
+        assert!(err.is_none());
+    }
+
+    #[test]
+    fn undefined_variables() {
+        let (_, err) = run("print missing;");
+        assert_eq!("Undefined variable 'missing'.", err.unwrap().message);
+
+        let (_, err) = run("{ var a = 1; } a = 2;");
+        assert_eq!("Undefined variable 'a'.", err.unwrap().message);
+    }
+
+    #[test]
+    fn control_flow() {
+        let source = "
+            var a = 0;
+            if (a == 0) print \"zero\"; else print \"nonzero\";
+            while (a < 3) a = a + 1;
+            print a;
+            for (var i = 0; i < 3; i = i + 1) print i;
+        ";
+        let (output, err) = run(source);
+        assert_eq!("zero\n3\n0\n1\n2\n", output);
+        assert!(err.is_none());
+    }
+
+    #[test]
+    fn break_and_continue() {
+        let source = "
+            var i = 0;
+            while (true) {
+                i = i + 1;
+                if (i == 2) continue;
+                if (i > 4) break;
+                print i;
+            }
+            for (var j = 0; j < 10; j = j + 1) {
+                if (j < 7) continue;
+                print j;
+                for (;;) break;
+                if (j == 8) break;
+            }
+        ";
+        let (output, err) = run(source);
+        assert_eq!("1\n3\n4\n7\n8\n", output);
+        assert!(err.is_none());
+    }
+
+    #[test]
+    fn functions() {
+        let source = "
+            fun add(a, b) { return a + b; }
+            fun nothing() {}
+            fun early(n) {
+                while (true) {
+                    if (n > 2) return n;
+                    n = n + 1;
+                }
+            }
+            print add(1, 2);
+            print nothing();
+            print early(0);
+            print add;
+        ";
+        let (output, err) = run(source);
+        assert_eq!("3\nnil\n3\n<fn add>\n", output);
+        assert!(err.is_none());
+    }
+
+    #[test]
+    fn recursion() {
+        let source = "
+            fun fib(n) {
+                if (n < 2) return n;
+                return fib(n - 2) + fib(n - 1);
+            }
+            print fib(15);
+        ";
+        let (output, _) = run(source);
+        assert_eq!("610\n", output);
+    }
+
+    #[test]
+    fn closures() {
+        let source = "
+            fun makeCounter() {
+                var i = 0;
+                fun count() {
+                    i = i + 1;
+                    return i;
+                }
+                return count;
+            }
+            var a = makeCounter();
+            var b = makeCounter();
+            print a();
+            print a();
+            print b();
+        ";
+        let (output, err) = run(source);
+        assert_eq!("1\n2\n1\n", output);
+        assert!(err.is_none());
+    }
+
+    #[test]
+    fn closures_bind_statically() {
+        let source = "
+            var a = \"global\";
+            {
+                fun showA() { print a; }
+                showA();
+                var a = \"block\";
+                showA();
+                print a;
+            }
+        ";
+        let (output, err) = run(source);
+        assert_eq!("global\nglobal\nblock\n", output);
+        assert!(err.is_none());
+    }
+
+    #[test]
+    fn call_errors() {
+        let (_, err) = run("fun f(a) {} f(1, 2);");
+        assert_eq!("Expected 1 arguments but got 2.", err.unwrap().message);
+
+        let (_, err) = run("\"not a function\"();");
+        assert_eq!("Can only call functions and classes.", err.unwrap().message);
+
+        let (_, err) = run("clock(1);");
+        assert_eq!("Expected 0 arguments but got 1.", err.unwrap().message);
+    }
+
+    #[test]
+    fn stack_trace() {
+        let source = "fun inner() {\n  return -\"x\";\n}\nfun outer() {\n  inner();\n}\nouter();";
+        let (_, err) = run(source);
+        assert_eq!(
+            "Operand must be a number.\n[line 2]\nin inner(), called at [line 5]\nin outer(), called at [line 7]",
+            err.unwrap().to_string()
+        );
+    }
+
+    #[test]
+    fn classes() {
+        let source = "
+            class Point {
+                init(x, y) {
+                    this.x = x;
+                    this.y = y;
+                }
+                sum() { return this.x + this.y; }
+            }
+            var p = Point(1, 2);
+            print Point;
+            print p;
+            print p.sum();
+            p.x = 10;
+            print p.sum();
+        ";
+        let (output, err) = run(source);
+        assert_eq!("Point\nPoint instance\n3\n12\n", output);
+        assert!(err.is_none());
+    }
+
+    #[test]
+    fn bound_methods() {
+        let source = "
+            class Counter {
+                init() { this.n = 0; }
+                bump() { this.n = this.n + 1; return this.n; }
+            }
+            var c = Counter();
+            var bump = c.bump;
+            bump();
+            print bump();
+            print c.n;
+            // Fields shadow methods
+            c.bump = \"field\";
+            print c.bump;
+        ";
+        let (output, err) = run(source);
+        assert_eq!("2\n2\nfield\n", output);
+        assert!(err.is_none());
+    }
+
+    #[test]
+    fn initializers() {
+        let source = "
+            class A {
+                init(early) {
+                    this.set = true;
+                    if (early) return;
+                    this.late = true;
+                }
+            }
+            var a = A(true);
+            print a.set;
+            print a.init(false) == a;
+            print a.late;
+        ";
+        let (output, err) = run(source);
+        assert_eq!("true\ntrue\ntrue\n", output);
+        assert!(err.is_none());
+
+        let (_, err) = run("class A { init(a) {} } A();");
+        assert_eq!("Expected 1 arguments but got 0.", err.unwrap().message);
+    }
+
+    #[test]
+    fn property_errors() {
+        let (_, err) = run("class A {} A().missing;");
+        assert_eq!("Undefined property 'missing'.", err.unwrap().message);
+
+        let (_, err) = run("var a = 1; a.b;");
+        assert_eq!("Only instances have properties.", err.unwrap().message);
+
+        let (_, err) = run("\"s\".b = 1;");
+        assert_eq!("Only instances have fields.", err.unwrap().message);
+    }
+
+    #[test]
+    fn inheritance() {
+        let source = "
+            class A {
+                method() { return \"A method\"; }
+                name() { return \"A\"; }
+            }
+            class B < A {
+                method() { return \"B method\"; }
+                test() { return super.method(); }
+            }
+            class C < B {}
+            var c = C();
+            print c.method();
+            print c.test();
+            print c.name();
+        ";
+        let (output, err) = run(source);
+        assert_eq!("B method\nA method\nA\n", output);
+        assert!(err.is_none());
+    }
+
+    #[test]
+    fn super_binds_this() {
+        let source = "
+            class Base {
+                init(n) { this.n = n; }
+                describe() { return \"n = \" + str(this.n); }
+            }
+            class Derived < Base {
+                init(n) { super.init(n * 2); }
+                describe() { return \"derived, \" + super.describe(); }
+            }
+            print Derived(2).describe();
+            var describe = Derived(5).describe;
+            print describe();
+        ";
+        let (output, err) = run(source);
+        assert_eq!("derived, n = 4\nderived, n = 10\n", output);
+        assert!(err.is_none());
+    }
+
+    #[test]
+    fn superclass_errors() {
+        let (_, err) = run("var NotAClass = 1; class A < NotAClass {}");
+        assert_eq!("Superclass must be a class.", err.unwrap().message);
+
+        let (_, err) = run("class A {} class B < A { m() { return super.missing; } } B().m();");
+        assert_eq!("Undefined property 'missing'.", err.unwrap().message);
+    }
+
+    #[test]
+    fn static_methods() {
+        let source = "
+            class Math {
+                class square(n) { return n * n; }
+                class describe() { return \"I am \" + str(this); }
+            }
+            class MoreMath < Math {
+                class cube(n) { return n * Math.square(n); }
+            }
+            print Math.square(3);
+            print Math.describe();
+            print MoreMath.cube(2);
+            print MoreMath.square(4);
+            print MoreMath.describe();
+        ";
+        let (output, err) = run(source);
+        assert_eq!("9\nI am Math\n8\n16\nI am MoreMath\n", output);
+        assert!(err.is_none());
+
+        // Static methods aren't available on instances, nor instance methods on the class
+        let (_, err) = run("class A { class s() {} } A().s();");
+        assert_eq!("Undefined property 's'.", err.unwrap().message);
+        let (_, err) = run("class A { m() {} } A.m();");
+        assert_eq!("Undefined property 'm'.", err.unwrap().message);
+    }
+
+    #[test]
+    fn super_in_static_methods() {
+        let source = "
+            class Shape {
+                class make() { return \"a \" + this.kind(); }
+                class kind() { return \"shape\"; }
+                make() { return \"instance\"; }
+            }
+            class Circle < Shape {
+                class make() { return super.make() + \"!\"; }
+                class kind() { return \"circle\"; }
+            }
+            print Circle.make();
+        ";
+        let (output, err) = run(source);
+        assert_eq!("a circle!\n", output);
+        assert!(err.is_none());
+
+        // Instance methods aren't found through `super` from a static method
+        let (_, err) = run("class A { m() {} } class B < A { class s() { super.m(); } } B.s();");
+        assert_eq!("Undefined property 'm'.", err.unwrap().message);
+    }
+
+    #[test]
+    fn getters() {
+        let source = "
+            class Rect {
+                init(w, h) { this.w = w; this.h = h; }
+                area { return this.w * this.h; }
+                class unit { return Rect(1, 1); }
+            }
+            class Square < Rect {
+                init(side) { super.init(side, side); }
+                description { return \"area \" + str(super.area); }
+            }
+            print Rect(2, 3).area;
+            print Rect.unit.area;
+            print Square(4).area;
+            print Square(5).description;
+            print Square.unit.area;
+        ";
+        let (output, err) = run(source);
+        assert_eq!("6\n1\n16\narea 25\n1\n", output);
+        assert!(err.is_none());
+    }
+
+    #[test]
+    fn traits() {
+        let source = "
+            class Animal {
+                init(name) { this.name = name; }
+                speak() { return \"...\"; }
+                describe() { return this.name + \" says \" + this.speak(); }
+            }
+            trait Walks {
+                move() { return this.name + \" walks\"; }
+            }
+            trait Barks {
+                speak() { return \"woof\"; }
+                legs { return 4; }
+            }
+            class Dog < Animal with Walks, Barks {}
+            var dog = Dog(\"Rex\");
+            print dog.describe();
+            print dog.move();
+            print dog.legs;
+            print Barks;
+        ";
+        let (output, err) = run(source);
+        assert_eq!("Rex says woof\nRex walks\n4\nBarks\n", output);
+        assert!(err.is_none());
+    }
+
+    #[test]
+    fn trait_conflicts() {
+        let conflicting = "
+            trait Barks { speak() { return \"woof\"; } }
+            trait Meows { speak() { return \"meow\"; } }
+        ";
+
+        let (_, err) = run(&format!("{conflicting} class Pet with Barks, Meows {{}}"));
+        let err = err.unwrap();
+        assert_eq!(
+            "Traits 'Barks' and 'Meows' both define 'speak'.",
+            err.message
+        );
+        assert_eq!("Meows", err.token.lexeme);
+
+        // Overriding the method in the class resolves the conflict
+        let (output, err) = run(&format!(
+            "{conflicting} class Pet with Barks, Meows {{ speak() {{ return \"hi\"; }} }} print Pet().speak();"
+        ));
+        assert_eq!("hi\n", output);
+        assert!(err.is_none());
+
+        let (_, err) = run("class A {} class B with A {}");
+        assert_eq!("Can only mix in traits.", err.unwrap().message);
+    }
+
+    #[test]
+    fn lists() {
+        let source = "
+            var xs = [1, \"two\", [3]];
+            print xs;
+            print xs[0];
+            print xs[-1][0];
+            xs[1] = 2;
+            print xs[1];
+            print xs[0:2];
+            print xs[-2:];
+            print xs[:10];
+            print xs[2:1];
+            var ys = xs;
+            ys[0] = 10;
+            print xs[0];
+            print [1, 2] == [1, 2];
+            print type(xs);
+        ";
+        let (output, err) = run(source);
+        assert_eq!(
+            "[1, two, [3]]\n1\n3\n2\n[1, 2]\n[2, [3]]\n[1, 2, [3]]\n[]\n10\ntrue\nlist\n",
+            output
+        );
+        assert!(err.is_none());
+    }
+
+    #[test]
+    fn index_errors() {
+        let source = "var xs = [1, 2, 3];\nprint xs  [3];";
+        let (_, err) = run(source);
+        let err = err.unwrap();
+        assert_eq!(
+            "Index 3 is out of range for a list of length 3.",
+            err.message
+        );
+        // Errors point at the whole subscript
+        assert_eq!("[3]", err.token.span.slice(source));
+        assert_eq!(2, err.token.line);
+
+        let (_, err) = run("[1][-2] = 0;");
+        assert_eq!(
+            "Index -2 is out of range for a list of length 1.",
+            err.unwrap().message
+        );
+
+        let (_, err) = run("[1][0.5];");
+        assert_eq!("Index must be a whole number.", err.unwrap().message);
+
+        let (_, err) = run("[1][\"a\"];");
+        assert_eq!(
+            "Index must be a number, not a string.",
+            err.unwrap().message
+        );
+
+        let (_, err) = run("var n = 1; n[0];");
+        assert_eq!(
+            "Only lists, maps and ranges can be indexed.",
+            err.unwrap().message
+        );
+    }
+
+    #[test]
+    fn maps() {
+        let source = "
+            var config = {\"name\": \"lox\", \"nested\": {\"depth\": 1}};
+            print config[\"name\"];
+            print config[\"nested\"][\"depth\"];
+            config[\"nested\"][\"depth\"] = 2;
+            config[1] = \"one\";
+            config[\"name\"] = \"renamed\";
+            print config;
+            print {1: 2} == {1: 2};
+            print {1: 2, 3: 4} == {3: 4, 1: 2};
+            print type({});
+        ";
+        let (output, err) = run(source);
+        assert_eq!(
+            "lox\n1\n{name: renamed, nested: {depth: 2}, 1: one}\ntrue\ntrue\nmap\n",
+            output
+        );
+        assert!(err.is_none());
+
+        let (_, err) = run("var m = {\"a\": 1}; m[\"b\"];");
+        assert_eq!("Key 'b' isn't in the map.", err.unwrap().message);
+
+        let (_, err) = run("var m = {[]: 1};");
+        assert_eq!("Can't use a list as a map key.", err.unwrap().message);
+    }
+
+    #[test]
+    fn for_in() {
+        let source = "
+            for (x in [1, 2, 3]) {
+                if (x == 2) continue;
+                print x;
+            }
+            for (var key in {\"a\": 1, \"b\": 2}) print key;
+            for (c in \"hi\") print c;
+            for (x in []) print \"never\";
+            for (x in [1, 2, 3]) { if (x == 2) break; print x; }
+        ";
+        let (output, err) = run(source);
+        assert_eq!("1\n3\na\nb\nh\ni\n1\n", output);
+        assert!(err.is_none());
+    }
+
+    #[test]
+    fn for_in_closures() {
+        let source = "
+            var fns = [];
+            for (x in [1, 2]) {
+                fun f() { return x; }
+                fns.push(f);
+            }
+            print fns[0]();
+            print fns[1]();
+        ";
+        let (output, _) = run(source);
+        assert_eq!("1\n2\n", output);
+    }
+
+    #[test]
+    fn iterator_protocol() {
+        let source = "
+            class Countdown {
+                init(from) { this.from = from; }
+                iterate() { return CountdownIterator(this.from); }
+            }
+            class CountdownIterator {
+                init(n) { this.n = n; }
+                hasNext() { return this.n > 0; }
+                next() {
+                    this.n = this.n - 1;
+                    return this.n + 1;
+                }
+            }
+            for (n in Countdown(3)) print n;
+        ";
+        let (output, err) = run(source);
+        assert_eq!("3\n2\n1\n", output);
+        assert!(err.is_none());
+
+        let (_, err) = run("for (x in 1) {}");
+        assert_eq!("Can't iterate over a number.", err.unwrap().message);
+
+        let (_, err) = run("class A {} for (x in A()) {}");
+        assert_eq!("Undefined property 'iterate'.", err.unwrap().message);
+
+        let (_, err) = run("class A { iterate() { return this; } } for (x in A()) {}");
+        assert_eq!("Undefined property 'hasNext'.", err.unwrap().message);
+    }
+
+    #[test]
+    fn membership() {
+        let source = "
+            print 2 in [1, 2];
+            print 3 in [1, 2];
+            print \"a\" in {\"a\": 1};
+            print [] in {\"a\": 1};
+            print \"ell\" in \"hello\";
+            print 2 in 1..3 and !(3 in 1..3);
+        ";
+        let (output, err) = run(source);
+        assert_eq!("true\nfalse\ntrue\nfalse\ntrue\ntrue\n", output);
+        assert!(err.is_none());
+
+        let (_, err) = run("print 1 in 2;");
+        assert_eq!(
+            "Can only look in lists, maps, ranges and strings.",
+            err.unwrap().message
+        );
+        let (_, err) = run("print 1 in \"1\";");
+        assert_eq!("Only strings can be in a string.", err.unwrap().message);
+    }
+}
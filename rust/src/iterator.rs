@@ -0,0 +1,100 @@
+use crate::ast::Ast;
+use crate::class;
+use crate::error::RuntimeError;
+use crate::interpreter::Interpreter;
+use crate::number::Number;
+use crate::range::Range;
+use crate::scanner::{Token, TokenLiteral, TokenType};
+use crate::value::Value;
+
+/// Where a `for-in` loop is up to in what it's iterating over
+pub enum LoxIterator {
+    /// The elements of a built-in collection, as they were when the loop started
+    Values(std::vec::IntoIter<Value>),
+    /// A range, along with how many of its numbers have been produced so far
+    Range(Range, Number),
+    /// An object implementing the iterator protocol: it has a `hasNext()` method saying whether
+    /// there are more values, and a `next()` method returning the next one
+    Object(Value),
+}
+
+impl LoxIterator {
+    /// Starts iterating over `iterable`. Lists give their elements, maps their keys, strings
+    /// their characters and ranges their numbers. Instances are asked for an iterator by calling
+    /// their `iterate()` method. `at` is where errors are reported.
+    pub fn new(
+        interpreter: &mut Interpreter,
+        ast: &Ast,
+        iterable: Value,
+        at: &Token,
+    ) -> Result<Self, RuntimeError> {
+        let values = match iterable {
+            Value::List(list) => list.borrow().clone(),
+            Value::Map(map) => map.borrow().entries().map(|(key, _)| key.clone()).collect(),
+            Value::String(s) => s.chars().map(|c| Value::String(c.to_string())).collect(),
+            Value::Range(range) => return Ok(LoxIterator::Range(range, Number::Int(0))),
+            Value::Instance(_) => {
+                let iterate = class::get(interpreter, ast, iterable, &method_name("iterate", at))?;
+                let iterator = interpreter.call_value(ast, iterate, vec![], at)?;
+                return Ok(LoxIterator::Object(iterator));
+            }
+            other => {
+                return Err(RuntimeError::new(
+                    at,
+                    format!("Can't iterate over a {}.", other.type_name()),
+                ));
+            }
+        };
+
+        Ok(LoxIterator::Values(values.into_iter()))
+    }
+
+    /// The next value, or `None` once there are no more
+    pub fn next(
+        &mut self,
+        interpreter: &mut Interpreter,
+        ast: &Ast,
+        at: &Token,
+    ) -> Result<Option<Value>, RuntimeError> {
+        match self {
+            LoxIterator::Values(values) => Ok(values.next()),
+            LoxIterator::Range(range, position) => {
+                if *position >= range.len() {
+                    return Ok(None);
+                }
+                let value = range.nth(position);
+                *position = position.add(&Number::Int(1));
+                Ok(Some(Value::Number(value)))
+            }
+            LoxIterator::Object(iterator) => {
+                let has_next = call_method(interpreter, ast, iterator, "hasNext", at)?;
+                if !has_next.is_truthy() {
+                    return Ok(None);
+                }
+                call_method(interpreter, ast, iterator, "next", at).map(Some)
+            }
+        }
+    }
+}
+
+/// Calls the method `name` on `object` without any arguments
+fn call_method(
+    interpreter: &mut Interpreter,
+    ast: &Ast,
+    object: &Value,
+    name: &str,
+    at: &Token,
+) -> Result<Value, RuntimeError> {
+    let method = class::get(interpreter, ast, object.clone(), &method_name(name, at))?;
+    interpreter.call_value(ast, method, vec![], at)
+}
+
+/// A token naming the protocol method `name`, for looking it up and reporting errors at `at`
+fn method_name(name: &str, at: &Token) -> Token {
+    Token::new(
+        TokenType::Identifier,
+        name.to_string(),
+        TokenLiteral::Str(name.to_string()),
+        at.span,
+    )
+}
@@ -0,0 +1,192 @@
+use crate::callable::NativeFunction;
+use crate::error::RuntimeError;
+use crate::native::length;
+use crate::number::Number;
+use crate::scanner::Token;
+use crate::value::Value;
+use num_bigint::{BigInt, Sign};
+use num_traits::ToPrimitive;
+use std::{cell::RefCell, fmt::Display, ops::Range, rc::Rc};
+
+/// A Lox list, shared between every value that refers to it
+pub type List = Rc<RefCell<Vec<Value>>>;
+
+/// Turns a Lox index into a position in a `collection` (as named in errors) of length `len`.
+/// Negative indices count back from the end.
+pub fn index(len: usize, index: &Value, collection: &str) -> Result<usize, String> {
+    let position = integer(index)?;
+    let position = if position < 0 {
+        position + len as i128
+    } else {
+        position
+    };
+    if position < 0 || position >= len as i128 {
+        return Err(out_of_range(index, collection, len));
+    }
+    Ok(position as usize)
+}
+
+/// Like `index`, for collections such as ranges that can be too long to count in a `usize`
+pub fn position(len: &Number, index: &Value, collection: &str) -> Result<Number, String> {
+    let zero = Number::Int(0);
+    let position = Number::big(whole(index)?);
+    let position = if position < zero {
+        position.add(len)
+    } else {
+        position
+    };
+    if position < zero || position >= *len {
+        return Err(out_of_range(index, collection, len));
+    }
+    Ok(position)
+}
+
+/// Like `index`, but for inserting into a list of length `len`, where inserting at `len`
+/// appends. Negative indices count back from just past the end, so `-1` appends too.
+fn insertion_index(len: usize, index: &Value) -> Result<usize, String> {
+    let position = integer(index)?;
+    let places = len as i128 + 1;
+    let position = if position < 0 {
+        position + places
+    } else {
+        position
+    };
+    if position < 0 || position >= places {
+        return Err(out_of_range(index, "list", len));
+    }
+    Ok(position as usize)
+}
+
+fn out_of_range(index: &Value, collection: &str, len: impl Display) -> String {
+    format!("Index {index} is out of range for a {collection} of length {len}.")
+}
+
+/// Turns the bounds of a slice into a range of positions in a list of length `len`. Missing
+/// bounds mean the start or end of the list, and bounds past either end are clamped to it.
+pub fn slice(
+    len: usize,
+    start: Option<&Value>,
+    end: Option<&Value>,
+) -> Result<Range<usize>, String> {
+    let bound = |bound: Option<&Value>, default: usize| -> Result<usize, String> {
+        let Some(bound) = bound else {
+            return Ok(default);
+        };
+        let bound = integer(bound)?;
+        let len = len as i128;
+        let position = if bound < 0 { bound + len } else { bound };
+        Ok(position.clamp(0, len) as usize)
+    };
+
+    let start = bound(start, 0)?;
+    let end = bound(end, len)?;
+    // A slice whose end comes before its start is empty
+    Ok(start..end.max(start))
+}
+
+/// The index as an integer. Indices too big for one are out of range anyway, so they're clamped.
+fn integer(index: &Value) -> Result<i128, String> {
+    let index = whole(index)?;
+    Ok(index.to_i128().unwrap_or(match index.sign() {
+        Sign::Minus => i128::MIN,
+        _ => i128::MAX,
+    }))
+}
+
+/// The index as a whole number of any size
+fn whole(index: &Value) -> Result<BigInt, String> {
+    match index {
+        Value::Number(n) => n
+            .to_bigint()
+            .ok_or_else(|| "Index must be a whole number.".to_string()),
+        other => Err(format!(
+            "Index must be a number, not a {}.",
+            other.type_name()
+        )),
+    }
+}
+
+/// Looks up the built-in method `name` on `list`, bound to it
+pub fn method(list: &List, name: &Token) -> Result<Value, RuntimeError> {
+    let list = Rc::clone(list);
+    let method = match name.lexeme.as_str() {
+        "push" => NativeFunction::new("push", 1, move |_, mut args| {
+            list.borrow_mut().push(args.remove(0));
+            Ok(Value::Nil)
+        }),
+        "pop" => NativeFunction::new("pop", 0, move |_, _| {
+            list.borrow_mut()
+                .pop()
+                .ok_or_else(|| "Can't pop from an empty list.".to_string())
+        }),
+        "insert" => NativeFunction::new("insert", 2, move |_, mut args| {
+            let mut list = list.borrow_mut();
+            let position = insertion_index(list.len(), &args[0])?;
+            list.insert(position, args.remove(1));
+            Ok(Value::Nil)
+        }),
+        "remove" => NativeFunction::new("remove", 1, move |_, args| {
+            let mut list = list.borrow_mut();
+            let position = index(list.len(), &args[0], "list")?;
+            Ok(list.remove(position))
+        }),
+        "len" => NativeFunction::new("len", 0, move |_, _| Ok(length(list.borrow().len()))),
+        "contains" => NativeFunction::new("contains", 1, move |_, args| {
+            Ok(Value::Boolean(list.borrow().contains(&args[0])))
+        }),
+        _ => {
+            return Err(RuntimeError::new(
+                name,
+                format!("Undefined property '{}'.", name.lexeme),
+            ));
+        }
+    };
+
+    Ok(Value::Callable(Rc::new(method)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::interpreter::tests::run;
+
+    #[test]
+    fn methods() {
+        let source = "
+            var xs = [1, 2];
+            xs.push(3);
+            xs.insert(0, 0);
+            xs.insert(4, 4);
+            print xs;
+            print xs.pop();
+            print xs.remove(-1);
+            print xs.len();
+            print xs.contains(1);
+            print xs.contains(9);
+            print xs;
+        ";
+        let (output, err) = run(source);
+        assert_eq!("[0, 1, 2, 3, 4]\n4\n3\n3\ntrue\nfalse\n[0, 1, 2]\n", output);
+        assert!(err.is_none());
+    }
+
+    #[test]
+    fn method_errors() {
+        let (_, err) = run("[].pop();");
+        assert_eq!("Can't pop from an empty list.", err.unwrap().message);
+
+        let (_, err) = run("[1].remove(1);");
+        assert_eq!(
+            "Index 1 is out of range for a list of length 1.",
+            err.unwrap().message
+        );
+
+        let (_, err) = run("[1].insert(5, 0);");
+        assert_eq!(
+            "Index 5 is out of range for a list of length 1.",
+            err.unwrap().message
+        );
+
+        let (_, err) = run("[1].sort();");
+        assert_eq!("Undefined property 'sort'.", err.unwrap().message);
+    }
+}
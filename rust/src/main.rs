@@ -1,70 +1,249 @@
+mod ast;
+mod callable;
+mod class;
+mod environment;
 mod error;
 mod expression;
+mod interpreter;
+mod iterator;
+mod list;
+mod map;
+mod native;
+mod number;
+mod parser;
+mod range;
+mod resolver;
 mod scanner;
+mod span;
+mod statement;
+mod value;
 
 use anyhow::{Error, anyhow};
 use std::{
-    env,
-    fs::File,
-    io::{self, BufRead, BufReader, Write},
+    env, fs,
+    io::{self, Write},
     path::PathBuf,
+    process,
 };
 
+use crate::ast::{Ast, StmtId};
+use crate::error::{LoxError, runtime_error, token_error};
+use crate::interpreter::{Division, Interpreter};
+use crate::parser::ParseError;
+use crate::parser::Parser;
+use crate::resolver::{ResolveError, Resolver};
 use crate::scanner::Scanner;
 
 fn main() -> Result<(), Error> {
-    let args: Vec<String> = env::args().collect();
-    match args.len() {
-        1 => run_prompt(),
-        2 => {
-            // run_file(PathBuf::from(args[1].to_owned()))
-            todo!("Figure out how to implement multi-line quotes and block comments");
-        }
-        _ => Err(anyhow!("Usage: lox [script]")),
-    }
-}
+    let mut args: Vec<String> = env::args().skip(1).collect();
 
-fn run_prompt() -> Result<(), Error> {
-    loop {
-        print!("Input Lox: ");
-        // Make sure prompt happens first
-        io::stdout().flush().unwrap();
-
-        let mut line = String::new();
-        io::stdin().read_line(&mut line).unwrap();
-        let line = line.trim_end().to_string();
-        if line.is_empty() {
-            break;
-        }
+    // Dividing by zero gives an infinity or NaN unless asked to fail instead
+    let mut division = Division::Ieee;
+    if let Some(flag) = args.iter().position(|arg| arg == "--strict-division") {
+        args.remove(flag);
+        division = Division::Strict;
+    }
 
-        run(line)?;
+    match args.as_slice() {
+        [] => run_prompt(division),
+        [script] => run_file(PathBuf::from(script), division),
+        _ => Err(anyhow!("Usage: lox [--strict-division] [script]")),
     }
+}
 
-    Ok(())
+fn run_prompt(division: Division) -> Result<(), Error> {
+    Lox::new(division).prompt()
 }
 
-fn run_file(path: PathBuf) -> Result<(), Error> {
+fn run_file(path: PathBuf, division: Division) -> Result<(), Error> {
     if !path.exists() {
         return Err(anyhow!("File does not exist"));
     }
 
-    let file = File::open(path)?;
-    let reader = BufReader::new(file);
-    for line in reader.lines() {
-        let line = line?;
-        run(line)?;
+    // The whole file is scanned at once so that strings and block comments can span lines
+    let source = fs::read_to_string(path)?;
+    match Lox::new(division).run(source) {
+        Ok(()) => Ok(()),
+        Err(e) => process::exit(e.exit_code()),
+    }
+}
+
+/// An interpreter session, which keeps its state between runs
+struct Lox {
+    ast: Ast,
+    interpreter: Interpreter,
+}
+
+impl Lox {
+    fn new(division: Division) -> Self {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_division(division);
+        Self::with_interpreter(interpreter)
+    }
+
+    fn with_interpreter(interpreter: Interpreter) -> Self {
+        Self {
+            ast: Ast::new(),
+            interpreter,
+        }
+    }
+
+    /// Runs lines typed at the prompt until an empty one. Lines are read through the interpreter
+    /// so that they share a buffer with `input()`, which would otherwise read ahead and swallow
+    /// them.
+    fn prompt(&mut self) -> Result<(), Error> {
+        loop {
+            print!("Input Lox: ");
+            // Make sure prompt happens first
+            io::stdout().flush()?;
+
+            let Some(line) = self.interpreter.read_line()? else {
+                break;
+            };
+            let line = line.trim_end().to_string();
+            if line.is_empty() {
+                break;
+            }
+
+            // Errors have already been reported, and the prompt carries on regardless
+            let _ = self.run_line(line);
+        }
+
+        Ok(())
+    }
+
+    /// Runs a whole program
+    fn run(&mut self, source: String) -> Result<(), LoxError> {
+        let tokens = Scanner::new(source).scan_tokens()?;
+        let statements = match Parser::new(tokens, &mut self.ast).parse() {
+            Ok(statements) => statements,
+            Err(errors) => return Err(report_parse_errors(&errors)),
+        };
+
+        self.execute(&statements)
+    }
+
+    /// Runs a line typed at the prompt. A line that is a bare expression rather than a list of
+    /// statements is evaluated and its value printed.
+    fn run_line(&mut self, line: String) -> Result<(), LoxError> {
+        let tokens = Scanner::new(line).scan_tokens()?;
+        let errors = match Parser::new(tokens.clone(), &mut self.ast).parse() {
+            Ok(statements) => return self.execute(&statements),
+            Err(errors) => errors,
+        };
+
+        // Only the errors from parsing statements are reported, since those are what was meant
+        let Ok(expr) = Parser::new(tokens, &mut self.ast).parse_expression() else {
+            return Err(report_parse_errors(&errors));
+        };
+
+        if let Err(errors) =
+            Resolver::new(&self.ast, &mut self.interpreter).resolve_expression(expr)
+        {
+            return Err(report_resolve_errors(&errors));
+        }
+
+        match self.interpreter.evaluate(&self.ast, expr) {
+            Ok(value) => {
+                println!("{value}");
+                Ok(())
+            }
+            Err(e) => {
+                runtime_error(&e);
+                Err(LoxError::Runtime)
+            }
+        }
     }
 
-    Ok(())
+    /// Resolves and then interprets statements that have been parsed into the session's `Ast`
+    fn execute(&mut self, statements: &[StmtId]) -> Result<(), LoxError> {
+        if let Err(errors) = Resolver::new(&self.ast, &mut self.interpreter).resolve(statements) {
+            return Err(report_resolve_errors(&errors));
+        }
+
+        self.interpreter
+            .interpret(&self.ast, statements)
+            .map_err(|e| {
+                runtime_error(&e);
+                LoxError::Runtime
+            })
+    }
 }
 
-fn run(line: String) -> Result<(), Error> {
-    let mut s = Scanner::new(line.to_owned());
-    let tokens = s.scan_tokens();
+fn report_parse_errors(errors: &[ParseError]) -> LoxError {
+    for e in errors {
+        token_error(&e.token, &e.message);
+    }
+    LoxError::Static
+}
 
-    for token in tokens.iter() {
-        println!("TOKEN -> {token}");
+fn report_resolve_errors(errors: &[ResolveError]) -> LoxError {
+    for e in errors {
+        token_error(&e.token, &e.message);
     }
+    LoxError::Static
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::tests::SharedBuffer;
 
-    Ok(())
+    /// A session that reads `input()` from `input`, along with where its output goes
+    fn session(input: &str) -> (Lox, SharedBuffer) {
+        let output = SharedBuffer::default();
+        let interpreter = Interpreter::with_io(
+            Box::new(io::Cursor::new(input.to_string())),
+            Box::new(output.clone()),
+        );
+        (Lox::with_interpreter(interpreter), output)
+    }
+
+    /// Feeds `input` to a prompt session, returning what its programs printed
+    fn prompt(input: &str) -> String {
+        let (mut lox, output) = session(input);
+        lox.prompt().unwrap();
+        output.contents()
+    }
+
+    /// Runs `source` as a script, returning what it printed and the code it would exit with
+    fn run(source: &str) -> (String, i32) {
+        let (mut lox, output) = session("");
+        let code = lox
+            .run(source.to_string())
+            .map_or_else(|e| e.exit_code(), |()| 0);
+        (output.contents(), code)
+    }
+
+    #[test]
+    fn exit_codes() {
+        assert_eq!(("1\n".to_string(), 0), run("print 1;"));
+        // Static errors stop anything from running
+        assert_eq!((String::new(), 65), run("print 1; print ;"));
+        assert_eq!((String::new(), 65), run("print 1; @"));
+        assert_eq!((String::new(), 65), run("print 1; return 2;"));
+        // Runtime errors stop the program where they happen
+        assert_eq!(
+            ("1\n".to_string(), 70),
+            run("print 1; print nil + 1; print 2;")
+        );
+    }
+
+    #[test]
+    fn prompt_carries_on_after_errors() {
+        let output = prompt("print nil + 1;\nprint ;\n@\nvar a = 1;\nprint a;\n");
+        assert_eq!("1\n", output);
+    }
+
+    #[test]
+    fn prompt_shares_input() {
+        let output = prompt("print input();\nread by input\nprint 1;\nprint 2;\n");
+        assert_eq!("read by input\n1\n2\n", output);
+    }
+
+    #[test]
+    fn prompt_recovers_from_failed_class() {
+        let output = prompt("class A {}\nclass B < A with A {}\nvar y = 2;\nprint y;\n");
+        assert_eq!("2\n", output);
+    }
 }
@@ -0,0 +1,206 @@
+use crate::callable::NativeFunction;
+use crate::error::RuntimeError;
+use crate::native::length;
+use crate::number::Number;
+use crate::scanner::Token;
+use crate::value::Value;
+use num_bigint::BigInt;
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+/// The hashable form of a value that can be used as a map key. Whole floats are hashed as
+/// integers and other floats by their bits, so that keys which compare equal, like `1`, `1.0`,
+/// `0` and `-0.0`, hash the same.
+#[derive(PartialEq, Eq, Hash, Debug)]
+enum Key {
+    Nil,
+    Boolean(bool),
+    Int(i64),
+    Big(BigInt),
+    Float(u64),
+    String(String),
+}
+
+impl Key {
+    fn new(value: &Value) -> Result<Self, String> {
+        match value {
+            Value::Nil => Ok(Key::Nil),
+            Value::Boolean(b) => Ok(Key::Boolean(*b)),
+            Value::Number(n) => match (n.to_i64(), n.to_bigint(), n) {
+                (Some(i), _, _) => Ok(Key::Int(i)),
+                (None, Some(big), _) => Ok(Key::Big(big)),
+                (None, None, Number::Float(f)) if f.is_nan() => {
+                    Err("Can't use NaN as a map key.".to_string())
+                }
+                (None, None, n) => Ok(Key::Float(n.to_f64().to_bits())),
+            },
+            Value::String(s) => Ok(Key::String(s.to_owned())),
+            other => Err(format!("Can't use a {} as a map key.", other.type_name())),
+        }
+    }
+}
+
+/// A map from values to values that remembers the order its keys were first inserted in
+#[derive(Default, Debug)]
+pub struct Map {
+    entries: Vec<(Value, Value)>,
+    /// Where each key's entry is in `entries`
+    positions: HashMap<Key, usize>,
+}
+
+impl Map {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn get(&self, key: &Value) -> Result<Option<&Value>, String> {
+        let position = self.positions.get(&Key::new(key)?);
+        Ok(position.map(|position| &self.entries[*position].1))
+    }
+
+    /// Sets the value for `key`, keeping its original place if it's already there
+    pub fn insert(&mut self, key: Value, value: Value) -> Result<(), String> {
+        let hashed = Key::new(&key)?;
+        match self.positions.get(&hashed) {
+            Some(position) => self.entries[*position].1 = value,
+            None => {
+                self.positions.insert(hashed, self.entries.len());
+                self.entries.push((key, value));
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes `key`, returning its value if it was there
+    pub fn remove(&mut self, key: &Value) -> Result<Option<Value>, String> {
+        let Some(position) = self.positions.remove(&Key::new(key)?) else {
+            return Ok(None);
+        };
+
+        let (_, value) = self.entries.remove(position);
+        // Every later entry has moved down one
+        for later in self.positions.values_mut() {
+            if *later > position {
+                *later -= 1;
+            }
+        }
+        Ok(Some(value))
+    }
+
+    /// The entries in insertion order
+    pub fn entries(&self) -> impl Iterator<Item = &(Value, Value)> {
+        self.entries.iter()
+    }
+}
+
+/// The error for looking up a key that isn't in a map
+pub fn missing_key(key: &Value) -> String {
+    format!("Key '{key}' isn't in the map.")
+}
+
+/// Looks up the built-in method `name` on `map`, bound to it
+pub fn method(map: &Rc<RefCell<Map>>, name: &Token) -> Result<Value, RuntimeError> {
+    let map = Rc::clone(map);
+    let method = match name.lexeme.as_str() {
+        "keys" => NativeFunction::new("keys", 0, move |_, _| {
+            let keys = map.borrow().entries().map(|(key, _)| key.clone()).collect();
+            Ok(Value::List(Rc::new(RefCell::new(keys))))
+        }),
+        "values" => NativeFunction::new("values", 0, move |_, _| {
+            let values = map
+                .borrow()
+                .entries()
+                .map(|(_, value)| value.clone())
+                .collect();
+            Ok(Value::List(Rc::new(RefCell::new(values))))
+        }),
+        "has" => NativeFunction::new("has", 1, move |_, args| {
+            Ok(Value::Boolean(map.borrow().get(&args[0])?.is_some()))
+        }),
+        "remove" => NativeFunction::new("remove", 1, move |_, args| {
+            map.borrow_mut()
+                .remove(&args[0])?
+                .ok_or_else(|| missing_key(&args[0]))
+        }),
+        "len" => NativeFunction::new("len", 0, move |_, _| Ok(length(map.borrow().len()))),
+        _ => {
+            return Err(RuntimeError::new(
+                name,
+                format!("Undefined property '{}'.", name.lexeme),
+            ));
+        }
+    };
+
+    Ok(Value::Callable(Rc::new(method)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::tests::run;
+
+    #[test]
+    fn keys() {
+        let mut map = Map::new();
+        map.insert(Value::Number(Number::Int(0)), Value::Nil)
+            .unwrap();
+        // 0, 0.0 and -0.0 are equal, so they're the same key
+        assert!(
+            map.get(&Value::Number(Number::Float(-0.0)))
+                .unwrap()
+                .is_some()
+        );
+        assert!(
+            map.get(&Value::Number(Number::Float(0.5)))
+                .unwrap()
+                .is_none()
+        );
+        // As are integers too big for 64 bits and the floats they're equal to
+        let big = Number::parse_integer("18446744073709551616", 10).unwrap();
+        map.insert(Value::Number(big), Value::Nil).unwrap();
+        assert!(
+            map.get(&Value::Number(Number::Float(2f64.powi(64))))
+                .unwrap()
+                .is_some()
+        );
+        assert!(map.get(&Value::String("0".to_string())).unwrap().is_none());
+        assert!(map.get(&Value::Boolean(false)).unwrap().is_none());
+
+        assert_eq!(
+            Err("Can't use NaN as a map key.".to_string()),
+            map.insert(Value::Number(Number::Float(f64::NAN)), Value::Nil)
+        );
+        let list = Value::List(Rc::new(RefCell::new(vec![])));
+        assert_eq!(
+            Err("Can't use a list as a map key.".to_string()),
+            map.insert(list, Value::Nil)
+        );
+    }
+
+    #[test]
+    fn methods() {
+        let source = "
+            var m = {\"b\": 1, \"a\": 2, nil: 3, true: 4, 5: 6};
+            print m.keys();
+            print m.values();
+            print m.has(\"a\");
+            print m.has(\"z\");
+            print m.remove(\"b\");
+            m[\"b\"] = 7;
+            print m.keys();
+            print m.len();
+        ";
+        let (output, err) = run(source);
+        assert_eq!(
+            "[b, a, nil, true, 5]\n[1, 2, 3, 4, 6]\ntrue\nfalse\n1\n[a, nil, true, 5, b]\n5\n",
+            output
+        );
+        assert!(err.is_none());
+
+        let (_, err) = run("var m = {}; m.remove(1);");
+        assert_eq!("Key '1' isn't in the map.", err.unwrap().message);
+    }
+}
@@ -0,0 +1,200 @@
+use crate::callable::{Callable, NativeFunction};
+use crate::environment::Environment;
+use crate::number::Number;
+use crate::value::Value;
+use std::{
+    rc::Rc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Every function implemented in Rust, as (name, arity, implementation). Adding a built-in only
+/// needs an entry here.
+fn registry() -> Vec<NativeFunction> {
+    vec![
+        NativeFunction::new("clock", 0, |_, _| {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_err(|e| e.to_string())?;
+            Ok(Value::Number(Number::Float(now.as_secs_f64())))
+        }),
+        NativeFunction::new("str", 1, |_, args| Ok(Value::String(args[0].to_string()))),
+        NativeFunction::new("num", 1, |_, args| match &args[0] {
+            Value::Number(n) => Ok(Value::Number(n.clone())),
+            Value::String(s) => {
+                let s = s.trim();
+                // Whole numbers become integers, like literals do
+                Number::parse_integer(s, 10)
+                    .or_else(|| s.parse().ok().map(Number::Float))
+                    .map(Value::Number)
+                    .ok_or_else(|| format!("Can't convert '{s}' to a number."))
+            }
+            other => Err(format!(
+                "Can't convert a {} to a number.",
+                other.type_name()
+            )),
+        }),
+        NativeFunction::new("toBase", 2, |_, args| {
+            let base = base(&args[1])?;
+            match &args[0] {
+                Value::Number(n) => n
+                    .to_string_in(base)
+                    .map(Value::String)
+                    .ok_or_else(|| "Only integers can be written in another base.".to_string()),
+                other => Err(format!(
+                    "Can't write a {} in another base.",
+                    other.type_name()
+                )),
+            }
+        }),
+        NativeFunction::new("fromBase", 2, |_, args| {
+            let base = base(&args[1])?;
+            match &args[0] {
+                Value::String(s) => Number::parse_integer(s.trim(), base)
+                    .map(Value::Number)
+                    .ok_or_else(|| format!("Can't read '{s}' as an integer in base {base}.")),
+                other => Err(format!("Can't read a {} as an integer.", other.type_name())),
+            }
+        }),
+        NativeFunction::new("len", 1, |_, args| match &args[0] {
+            Value::String(s) => Ok(length(s.chars().count())),
+            Value::List(list) => Ok(length(list.borrow().len())),
+            Value::Map(map) => Ok(length(map.borrow().len())),
+            Value::Range(range) => Ok(Value::Number(range.len())),
+            other => Err(format!("Can't get the length of a {}.", other.type_name())),
+        }),
+        NativeFunction::new("type", 1, |_, args| {
+            Ok(Value::String(args[0].type_name().to_string()))
+        }),
+        NativeFunction::new("input", 0, |interpreter, _| {
+            // Returns nil once there's nothing left to read
+            match interpreter.read_line().map_err(|e| e.to_string())? {
+                Some(line) => Ok(Value::String(line)),
+                None => Ok(Value::Nil),
+            }
+        }),
+    ]
+}
+
+/// Checks that `base` is a base numbers can be written in, from binary to using every letter
+fn base(base: &Value) -> Result<u32, String> {
+    match base {
+        Value::Number(n) => match n.to_i64() {
+            Some(base @ 2..=36) => Ok(base as u32),
+            _ => Err(format!("Base must be between 2 and 36, not {n}.")),
+        },
+        other => Err(format!(
+            "Base must be a number, not a {}.",
+            other.type_name()
+        )),
+    }
+}
+
+/// The value for the length of a collection
+pub fn length(len: usize) -> Value {
+    Value::Number(Number::Int(len as i64))
+}
+
+/// Defines every native function in `globals`
+pub fn install(globals: &mut Environment) {
+    for native in registry() {
+        let name = native.name().to_string();
+        globals.define(&name, Value::Callable(Rc::new(native)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::interpreter::tests::{run, run_with_input};
+
+    #[test]
+    fn clock() {
+        let (output, err) = run("var start = clock(); print clock() >= start;");
+        assert_eq!("true\n", output);
+        assert!(err.is_none());
+    }
+
+    #[test]
+    fn str() {
+        let (output, _) = run("print str(3) + str(true) + str(nil) + str(\"s\");");
+        assert_eq!("3truenils\n", output);
+    }
+
+    #[test]
+    fn num() {
+        let (output, _) = run("print num(\" 2.5 \") * 2; print num(4); print num(\"12\");");
+        assert_eq!("5.0\n4\n12\n", output);
+        let (output, _) = run("print num(\"-12345678901234567890\") + 1;");
+        assert_eq!("-12345678901234567889\n", output);
+
+        let (_, err) = run("num(\"abc\");");
+        assert_eq!("Can't convert 'abc' to a number.", err.unwrap().message);
+        let (_, err) = run("num(\"1_000\");");
+        assert_eq!("Can't convert '1_000' to a number.", err.unwrap().message);
+    }
+
+    #[test]
+    fn bases() {
+        let source = "
+            print toBase(255, 16);
+            print toBase(-5, 2);
+            print toBase(2 ** 64, 16);
+            print fromBase(\"ff\", 16);
+            print fromBase(\"-101\", 2);
+            print fromBase(\"zzzzzzzzzzzzzzzz\", 36);
+            print fromBase(toBase(12345678901234567890, 7), 7);
+        ";
+        let (output, err) = run(source);
+        assert_eq!(
+            "ff\n-101\n10000000000000000\n255\n-5\n7958661109946400884391935\n12345678901234567890\n",
+            output
+        );
+        assert!(err.is_none());
+
+        let (_, err) = run("toBase(1.5, 2);");
+        assert_eq!(
+            "Only integers can be written in another base.",
+            err.unwrap().message
+        );
+        let (_, err) = run("fromBase(\"12\", 2);");
+        assert_eq!(
+            "Can't read '12' as an integer in base 2.",
+            err.unwrap().message
+        );
+        let (_, err) = run("fromBase(\"1_0\", 10);");
+        assert_eq!(
+            "Can't read '1_0' as an integer in base 10.",
+            err.unwrap().message
+        );
+        let (_, err) = run("toBase(1, 37);");
+        assert_eq!(
+            "Base must be between 2 and 36, not 37.",
+            err.unwrap().message
+        );
+    }
+
+    #[test]
+    fn len() {
+        let (output, _) = run("print len(\"hello\"); print len(\"\"); print len([1, 2]);");
+        assert_eq!("5\n0\n2\n", output);
+
+        let (_, err) = run("len(1);");
+        assert_eq!("Can't get the length of a number.", err.unwrap().message);
+    }
+
+    #[test]
+    fn type_of() {
+        let (output, _) = run(
+            "print type(1); print type(\"\"); print type(nil); print type(false); print type(clock);",
+        );
+        assert_eq!("number\nstring\nnil\nboolean\nfunction\n", output);
+    }
+
+    #[test]
+    fn input() {
+        let (output, _) = run_with_input(
+            "print input() + \"!\"; print input(); print input();",
+            "first line\nsecond\n",
+        );
+        assert_eq!("first line!\nsecond\nnil\n", output);
+    }
+}
@@ -0,0 +1,393 @@
+use num_bigint::{BigInt, Sign};
+use num_integer::Integer;
+use num_traits::{FromPrimitive, Num, ToPrimitive, Zero};
+use std::{cmp::Ordering, fmt::Display, rc::Rc};
+
+/// A Lox number. Integer literals and arithmetic on integers stay exact, growing past 64 bits
+/// as needed; anything involving a float, or dividing with `/`, gives a float.
+#[derive(Clone, Debug)]
+pub enum Number {
+    Int(i64),
+    /// An integer too big for an `Int`. Shared, since numbers are copied around a lot.
+    Big(Rc<BigInt>),
+    Float(f64),
+}
+
+/// 2^63, the first float past the largest `i64`
+const INT_LIMIT: f64 = 9_223_372_036_854_775_808.0;
+
+/// The most bits a power of integers may have, which keeps something like `10 ** 4000000000`
+/// from exhausting memory
+const MAX_POWER_BITS: u64 = 1 << 20;
+
+impl Number {
+    /// The smallest representation of `n`, so that an integer is only `Big` if it has to be
+    pub fn big(n: BigInt) -> Number {
+        match n.to_i64() {
+            Some(i) => Number::Int(i),
+            None => Number::Big(Rc::new(n)),
+        }
+    }
+
+    /// Parses an integer written in `base`, with an optional sign
+    pub fn parse_integer(text: &str, base: u32) -> Option<Number> {
+        // `BigInt` would also accept `_` between digits, which integer literals don't allow
+        let digits = text.strip_prefix(['+', '-']).unwrap_or(text);
+        if digits.is_empty() || !digits.chars().all(|c| c.is_digit(base)) {
+            return None;
+        }
+
+        match i64::from_str_radix(text, base) {
+            Ok(i) => Some(Number::Int(i)),
+            Err(_) => BigInt::from_str_radix(text, base).ok().map(Number::big),
+        }
+    }
+
+    /// Writes an integer in `base`, or `None` for a float
+    pub fn to_string_in(&self, base: u32) -> Option<String> {
+        match self {
+            Number::Int(i) => Some(BigInt::from(*i).to_str_radix(base)),
+            Number::Big(n) => Some(n.to_str_radix(base)),
+            Number::Float(_) => None,
+        }
+    }
+
+    /// The nearest float, which is infinite for integers too big for one
+    pub fn to_f64(&self) -> f64 {
+        match self {
+            Number::Int(i) => *i as f64,
+            Number::Big(n) => n.to_f64().unwrap_or(f64::NAN),
+            Number::Float(f) => *f,
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.to_f64() == 0.0
+    }
+
+    /// The number as an integer, if it's a whole number that fits in one
+    pub fn to_i64(&self) -> Option<i64> {
+        match self {
+            Number::Int(i) => Some(*i),
+            Number::Float(f) if f.fract() == 0.0 && (-INT_LIMIT..INT_LIMIT).contains(f) => {
+                Some(*f as i64)
+            }
+            Number::Big(_) | Number::Float(_) => None,
+        }
+    }
+
+    /// The number as an integer of any size, if it's a whole number
+    pub fn to_bigint(&self) -> Option<BigInt> {
+        match self {
+            Number::Int(i) => Some(BigInt::from(*i)),
+            Number::Big(n) => Some(BigInt::clone(n)),
+            Number::Float(f) if f.fract() == 0.0 => BigInt::from_f64(*f),
+            Number::Float(_) => None,
+        }
+    }
+
+    pub fn add(&self, other: &Number) -> Number {
+        self.arithmetic(other, i64::checked_add, |l, r| l + r, |l, r| l + r)
+    }
+
+    pub fn subtract(&self, other: &Number) -> Number {
+        self.arithmetic(other, i64::checked_sub, |l, r| l - r, |l, r| l - r)
+    }
+
+    pub fn multiply(&self, other: &Number) -> Number {
+        self.arithmetic(other, i64::checked_mul, |l, r| l * r, |l, r| l * r)
+    }
+
+    pub fn negate(&self) -> Number {
+        match self {
+            Number::Int(i) => i
+                .checked_neg()
+                .map_or_else(|| Number::big(-BigInt::from(*i)), Number::Int),
+            Number::Big(n) => Number::big(-BigInt::clone(n)),
+            Number::Float(f) => Number::Float(-f),
+        }
+    }
+
+    /// `/`, which always gives a float so that `1 / 2` is `0.5`
+    pub fn divide(&self, other: &Number) -> Number {
+        Number::Float(self.to_f64() / other.to_f64())
+    }
+
+    /// `~/`, rounding towards negative infinity
+    pub fn floor_divide(&self, other: &Number) -> Result<Number, String> {
+        self.division(other, Integer::div_floor, Integer::div_floor, |l, r| {
+            (l / r).floor()
+        })
+    }
+
+    /// `%`, floored so that the result takes the sign of the divisor
+    pub fn modulo(&self, other: &Number) -> Result<Number, String> {
+        self.division(other, Integer::mod_floor, Integer::mod_floor, |l, r| {
+            l - r * (l / r).floor()
+        })
+    }
+
+    /// `**`. An integer raised to a negative power is a float.
+    pub fn power(&self, other: &Number) -> Result<Number, String> {
+        let (Some(base), Some(exponent)) = (self.to_integer(), other.to_integer()) else {
+            return Ok(Number::Float(self.to_f64().powf(other.to_f64())));
+        };
+        if exponent.sign() == Sign::Minus {
+            return Ok(Number::Float(self.to_f64().powf(other.to_f64())));
+        }
+        if exponent.is_zero() {
+            return Ok(Number::Int(1));
+        }
+
+        match base.to_i64() {
+            // Only these bases give something small whatever the exponent
+            Some(0 | 1) => return Ok(Number::big(base)),
+            Some(-1) => return Ok(Number::Int(if exponent.is_even() { 1 } else { -1 })),
+            _ => {}
+        }
+
+        // Each factor of the base adds at least `bits - 1` bits, so results too big to hold are
+        // refused before trying to work them out
+        let exponent = exponent
+            .to_u64()
+            .filter(|exponent| exponent.saturating_mul(base.bits() - 1) <= MAX_POWER_BITS)
+            .ok_or_else(|| "Exponent is too large.".to_string())?;
+        // No more than `MAX_POWER_BITS`, so it fits
+        Ok(Number::big(base.pow(exponent as u32)))
+    }
+
+    /// The number as an integer if it's an `Int` or `Big`, but not a float
+    fn to_integer(&self) -> Option<BigInt> {
+        match self {
+            Number::Float(_) => None,
+            integer => integer.to_bigint(),
+        }
+    }
+
+    /// Applies `int` when both numbers are `Int`s, falling back to `big` if that overflows or
+    /// either is `Big`, and applies `float` when either is a float
+    fn arithmetic(
+        &self,
+        other: &Number,
+        int: fn(i64, i64) -> Option<i64>,
+        big: fn(BigInt, BigInt) -> BigInt,
+        float: fn(f64, f64) -> f64,
+    ) -> Number {
+        if let (Number::Int(l), Number::Int(r)) = (self, other)
+            && let Some(result) = int(*l, *r)
+        {
+            return Number::Int(result);
+        }
+
+        match (self.to_integer(), other.to_integer()) {
+            (Some(l), Some(r)) => Number::big(big(l, r)),
+            _ => Number::Float(float(self.to_f64(), other.to_f64())),
+        }
+    }
+
+    /// Like `arithmetic` for the operators that divide, which fail when dividing an integer by
+    /// zero since there's no integer infinity
+    fn division(
+        &self,
+        other: &Number,
+        int: fn(&i64, &i64) -> i64,
+        big: fn(&BigInt, &BigInt) -> BigInt,
+        float: fn(f64, f64) -> f64,
+    ) -> Result<Number, String> {
+        match (self, other) {
+            (Number::Float(_), _) | (_, Number::Float(_)) => {
+                Ok(Number::Float(float(self.to_f64(), other.to_f64())))
+            }
+            (_, Number::Int(0)) => Err("Division by zero.".to_string()),
+            // `i64::MIN` divided by -1 is the only quotient of two `Int`s that doesn't fit
+            (Number::Int(l), Number::Int(r)) if (*l, *r) != (i64::MIN, -1) => {
+                Ok(Number::Int(int(l, r)))
+            }
+            _ => {
+                let (l, r) = (self.to_bigint().unwrap(), other.to_bigint().unwrap());
+                Ok(Number::big(big(&l, &r)))
+            }
+        }
+    }
+}
+
+impl From<i64> for Number {
+    fn from(i: i64) -> Self {
+        Number::Int(i)
+    }
+}
+
+impl From<f64> for Number {
+    fn from(f: f64) -> Self {
+        Number::Float(f)
+    }
+}
+
+/// Numbers are equal when they have the same value, so `1 == 1.0`
+impl PartialEq for Number {
+    fn eq(&self, other: &Self) -> bool {
+        self.partial_cmp(other) == Some(Ordering::Equal)
+    }
+}
+
+/// Integers and floats are compared exactly, without converting the integer to a float first
+impl PartialOrd for Number {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self, other) {
+            (Number::Int(l), Number::Int(r)) => Some(l.cmp(r)),
+            (Number::Float(l), Number::Float(r)) => l.partial_cmp(r),
+            (Number::Int(l), Number::Float(r)) => compare_int_float(*l, *r),
+            (Number::Float(l), Number::Int(r)) => compare_int_float(*r, *l).map(Ordering::reverse),
+            (Number::Big(l), Number::Float(r)) => compare_big_float(l, *r),
+            (Number::Float(l), Number::Big(r)) => compare_big_float(r, *l).map(Ordering::reverse),
+            (l, r) => Some(l.to_bigint()?.cmp(&r.to_bigint()?)),
+        }
+    }
+}
+
+fn compare_int_float(int: i64, float: f64) -> Option<Ordering> {
+    if float.is_nan() {
+        return None;
+    }
+    if float >= INT_LIMIT {
+        return Some(Ordering::Less);
+    }
+    if float < -INT_LIMIT {
+        return Some(Ordering::Greater);
+    }
+
+    // The float's whole part fits in an integer now, and only its fraction can break a tie
+    match int.cmp(&(float.trunc() as i64)) {
+        Ordering::Equal => 0.0.partial_cmp(&float.fract()),
+        ordering => Some(ordering),
+    }
+}
+
+fn compare_big_float(big: &BigInt, float: f64) -> Option<Ordering> {
+    if float.is_infinite() {
+        return Some(if float > 0.0 {
+            Ordering::Less
+        } else {
+            Ordering::Greater
+        });
+    }
+
+    // Fails for NaN
+    let whole = BigInt::from_f64(float.trunc())?;
+    match big.cmp(&whole) {
+        Ordering::Equal => 0.0.partial_cmp(&float.fract()),
+        ordering => Some(ordering),
+    }
+}
+
+/// Floats always print with a `.`, an exponent or as `inf` or `NaN`, so they can't be mistaken for
+/// integers
+impl Display for Number {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Number::Int(i) => write!(f, "{i}"),
+            Number::Big(n) => write!(f, "{n}"),
+            Number::Float(float) => write!(f, "{float:?}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn big(text: &str) -> Number {
+        Number::parse_integer(text, 10).unwrap()
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!("3", Number::Int(3).to_string());
+        assert_eq!("3.0", Number::Float(3.0).to_string());
+        assert_eq!("-0.0", Number::Float(-0.0).to_string());
+        assert_eq!("2.5", Number::Float(2.5).to_string());
+        assert_eq!("1e20", Number::Float(1e20).to_string());
+        assert_eq!("inf", Number::Float(f64::INFINITY).to_string());
+        assert_eq!(
+            "100000000000000000000",
+            big("100000000000000000000").to_string()
+        );
+    }
+
+    #[test]
+    fn comparison() {
+        assert_eq!(Number::Int(1), Number::Float(1.0));
+        assert!(Number::Int(1) < Number::Float(1.5));
+        assert!(Number::Float(-0.5) < Number::Int(0));
+        assert_ne!(Number::Int(0), Number::Float(f64::NAN));
+        // 2^53 + 1 isn't a float, so converting it would make it equal to 2^53
+        assert!(Number::Int(9_007_199_254_740_993) > Number::Float(9_007_199_254_740_992.0));
+        assert!(Number::Int(i64::MAX) < Number::Float(INT_LIMIT));
+        assert!(Number::Int(i64::MIN) == Number::Float(-INT_LIMIT));
+
+        assert_eq!(big("9223372036854775808"), Number::Float(INT_LIMIT));
+        assert!(big("9223372036854775809") > Number::Float(INT_LIMIT));
+        assert!(big("-9223372036854775809") < Number::Int(i64::MIN));
+        assert!(big("100000000000000000000") < Number::Float(f64::INFINITY));
+        assert_ne!(big("100000000000000000000"), Number::Float(f64::NAN));
+    }
+
+    #[test]
+    fn promotion() {
+        let int = Number::Int;
+        assert!(matches!(int(2).add(&int(3)), Number::Int(5)));
+        assert!(matches!(int(2).add(&Number::Float(3.0)), Number::Float(_)));
+        assert_eq!(big("9223372036854775808"), int(i64::MAX).add(&int(1)));
+        assert_eq!(big("9223372036854775808"), int(i64::MIN).negate());
+        assert_eq!(
+            Ok(big("9223372036854775808")),
+            int(i64::MIN).floor_divide(&int(-1))
+        );
+        assert_eq!(Ok(big("12157665459056928801")), int(3).power(&int(40)));
+        // Results that fit go back to being `Int`s
+        assert!(matches!(
+            big("9223372036854775808").subtract(&int(1)),
+            Number::Int(i64::MAX)
+        ));
+        assert_eq!(Ok(int(-1)), int(-1).power(&int(i64::MAX)));
+        assert_eq!(Ok(int(1)), int(0).power(&int(0)));
+        assert_eq!(Ok(int(-1)), int(-1).power(&big("100000000000000000001")));
+        assert_eq!(Ok(int(1)), int(1).power(&big("100000000000000000000")));
+        let too_large = Err("Exponent is too large.".to_string());
+        assert_eq!(too_large, int(2).power(&int(i64::MAX)));
+        assert_eq!(too_large, int(10).power(&int(4_000_000_000)));
+        assert_eq!(too_large, int(2).power(&big("100000000000000000000")));
+        assert_eq!(too_large, big("100000000000000000000").power(&int(20_000)));
+    }
+
+    #[test]
+    fn division() {
+        let int = Number::Int;
+        assert!(matches!(int(4).divide(&int(2)), Number::Float(f) if f == 2.0));
+        assert_eq!(Ok(int(-4)), int(-7).floor_divide(&int(2)));
+        assert_eq!(Ok(int(2)), int(-7).modulo(&int(3)));
+        assert_eq!(Ok(int(-2)), int(7).modulo(&int(-3)));
+        assert_eq!(Ok(int(0)), int(i64::MIN).modulo(&int(-1)));
+        assert_eq!(Ok(int(1)), big("100000000000000000001").modulo(&int(10)));
+        assert_eq!(Err("Division by zero.".to_string()), int(1).modulo(&int(0)));
+    }
+
+    #[test]
+    fn bases() {
+        assert_eq!(Some(Number::Int(255)), Number::parse_integer("ff", 16));
+        assert_eq!(Some(Number::Int(-5)), Number::parse_integer("-101", 2));
+        assert_eq!(None, Number::parse_integer("12", 2));
+        assert_eq!(None, Number::parse_integer("1_000", 10));
+        assert_eq!(
+            None,
+            Number::parse_integer("1_000_000_000_000_000_000_000", 10)
+        );
+        assert_eq!(None, Number::parse_integer("-", 10));
+        assert_eq!(Some("-ff".to_string()), Number::Int(-255).to_string_in(16));
+        assert_eq!(
+            Some("1".to_string() + &"0".repeat(64)),
+            big("18446744073709551616").to_string_in(2)
+        );
+        assert_eq!(None, Number::Float(1.0).to_string_in(2));
+    }
+}
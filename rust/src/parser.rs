@@ -0,0 +1,1661 @@
+use crate::ast::{Ast, ExprId, StmtId};
+use crate::expression::{
+    Assign, Binary, Call, Comma, Conditional, Expression, Get, Grouping, Index, List, Literal,
+    Logical, Map, Set, SetIndex, Subscript, Super, This, Unary, Update, Variable,
+};
+use crate::scanner::{Token, TokenLiteral, TokenType};
+use crate::span::Span;
+use crate::statement::{Class, ForIn, Function, If, Print, Return, Statement, Trait, Var, While};
+use std::fmt::Display;
+
+#[derive(Debug)]
+pub struct ParseError {
+    pub token: Token,
+    pub message: String,
+}
+
+/// The most parameters a function can declare, or arguments a call can pass
+const MAX_ARITY: usize = 255;
+
+/// How tightly an operator binds, from loosest to tightest
+#[derive(Clone, Copy, PartialEq, PartialOrd, Debug)]
+enum Precedence {
+    None,
+    Comma,
+    Assignment,
+    Conditional,
+    Or,
+    And,
+    Equality,
+    Comparison,
+    Range,
+    Term,
+    Factor,
+    Unary,
+    Exponent,
+    Call,
+    Primary,
+}
+
+impl Precedence {
+    /// The next tighter level
+    fn next(self) -> Self {
+        match self {
+            Precedence::None => Precedence::Comma,
+            Precedence::Comma => Precedence::Assignment,
+            Precedence::Assignment => Precedence::Conditional,
+            Precedence::Conditional => Precedence::Or,
+            Precedence::Or => Precedence::And,
+            Precedence::And => Precedence::Equality,
+            Precedence::Equality => Precedence::Comparison,
+            Precedence::Comparison => Precedence::Range,
+            Precedence::Range => Precedence::Term,
+            Precedence::Term => Precedence::Factor,
+            Precedence::Factor => Precedence::Unary,
+            Precedence::Unary => Precedence::Exponent,
+            Precedence::Exponent => Precedence::Call,
+            Precedence::Call | Precedence::Primary => Precedence::Primary,
+        }
+    }
+}
+
+/// Which side a chain of operators at the same precedence groups towards
+#[derive(Clone, Copy)]
+enum Associativity {
+    Left,
+    Right,
+}
+
+/// What `function` is parsing. Only methods can be getters.
+#[derive(Clone, Copy, PartialEq)]
+enum FunctionKind {
+    Function,
+    Method,
+}
+
+impl Display for FunctionKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FunctionKind::Function => write!(f, "function"),
+            FunctionKind::Method => write!(f, "method"),
+        }
+    }
+}
+
+/// Parses an expression whose first token has just been consumed
+type PrefixFn<'a> = fn(&mut Parser<'a>) -> Result<ExprId, ParseError>;
+/// Parses the rest of an expression whose operator has just been consumed
+type InfixFn<'a> = fn(&mut Parser<'a>, ExprId) -> Result<ExprId, ParseError>;
+
+/// An entry in the Pratt parser's table, describing how a token behaves in expressions
+struct ParseRule<'a> {
+    prefix: Option<PrefixFn<'a>>,
+    infix: Option<InfixFn<'a>>,
+    precedence: Precedence,
+    associativity: Associativity,
+}
+
+/// Turns a list of tokens into nodes in an `Ast`. Statements are parsed by recursive descent and
+/// expressions by a Pratt parser driven by `Parser::rule`. Errors are collected rather than
+/// reported so that the caller can decide what to do with them.
+pub struct Parser<'a> {
+    tokens: Vec<Token>,
+    current: usize,
+    ast: &'a mut Ast,
+    errors: Vec<ParseError>,
+    /// How many loops enclose the statement being parsed
+    loop_depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    pub fn new(tokens: Vec<Token>, ast: &'a mut Ast) -> Self {
+        Self {
+            tokens,
+            current: 0,
+            ast,
+            errors: vec![],
+            loop_depth: 0,
+        }
+    }
+
+    /// Parses a whole program, returning every error found
+    pub fn parse(&mut self) -> Result<Vec<StmtId>, Vec<ParseError>> {
+        let mut statements = vec![];
+        while !self.is_at_end() {
+            match self.declaration() {
+                Ok(stmt) => statements.push(stmt),
+                Err(e) => {
+                    self.errors.push(e);
+                    self.synchronize();
+                }
+            }
+        }
+
+        if self.errors.is_empty() {
+            Ok(statements)
+        } else {
+            Err(std::mem::take(&mut self.errors))
+        }
+    }
+
+    /// Parses a single expression spanning all of the tokens, returning every error found
+    pub fn parse_expression(&mut self) -> Result<ExprId, Vec<ParseError>> {
+        let result = self.expression().and_then(|expr| {
+            if self.is_at_end() {
+                Ok(expr)
+            } else {
+                Err(self.error(self.peek().clone(), "Expect end of expression."))
+            }
+        });
+
+        match result {
+            Ok(expr) if self.errors.is_empty() => Ok(expr),
+            Ok(_) => Err(std::mem::take(&mut self.errors)),
+            Err(e) => {
+                self.errors.push(e);
+                Err(std::mem::take(&mut self.errors))
+            }
+        }
+    }
+
+    /// declaration -> classDecl | traitDecl | funDecl | varDecl | statement
+    fn declaration(&mut self) -> Result<StmtId, ParseError> {
+        if self.match_token(&[TokenType::Class]) {
+            return self.class_declaration();
+        }
+        if self.match_token(&[TokenType::Trait]) {
+            return self.trait_declaration();
+        }
+        if self.match_token(&[TokenType::Fun]) {
+            return self.function(FunctionKind::Function);
+        }
+        if self.match_token(&[TokenType::Var]) {
+            return self.var_declaration();
+        }
+
+        self.statement()
+    }
+
+    /// classDecl -> "class" IDENTIFIER ( "<" IDENTIFIER )?
+    ///              ( "with" IDENTIFIER ( "," IDENTIFIER )* )?
+    ///              "{" ( "class"? method )* "}"
+    fn class_declaration(&mut self) -> Result<StmtId, ParseError> {
+        let keyword = self.previous().clone();
+        let name = self.consume(TokenType::Identifier, "Expect class name.")?;
+
+        let superclass = if self.match_token(&[TokenType::Less]) {
+            Some(self.class_name("Expect superclass name.")?)
+        } else {
+            None
+        };
+
+        let mut traits = vec![];
+        if self.match_token(&[TokenType::With]) {
+            loop {
+                traits.push(self.class_name("Expect trait name.")?);
+                if !self.match_token(&[TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+        self.consume(TokenType::LeftBrace, "Expect '{' before class body.")?;
+
+        let mut methods = vec![];
+        let mut class_methods = vec![];
+        while !self.check(&TokenType::RightBrace) && !self.is_at_end() {
+            if self.match_token(&[TokenType::Class]) {
+                class_methods.push(self.function(FunctionKind::Method)?);
+            } else {
+                methods.push(self.function(FunctionKind::Method)?);
+            }
+        }
+
+        let right_brace = self.consume(TokenType::RightBrace, "Expect '}' after class body.")?;
+        Ok(self.ast.alloc_stmt(
+            Statement::Class(Class {
+                name,
+                superclass,
+                traits,
+                methods,
+                class_methods,
+            }),
+            keyword.span.to(right_brace.span),
+        ))
+    }
+
+    /// traitDecl -> "trait" IDENTIFIER "{" method* "}"
+    fn trait_declaration(&mut self) -> Result<StmtId, ParseError> {
+        let keyword = self.previous().clone();
+        let name = self.consume(TokenType::Identifier, "Expect trait name.")?;
+        self.consume(TokenType::LeftBrace, "Expect '{' before trait body.")?;
+
+        let mut methods = vec![];
+        while !self.check(&TokenType::RightBrace) && !self.is_at_end() {
+            methods.push(self.function(FunctionKind::Method)?);
+        }
+
+        let right_brace = self.consume(TokenType::RightBrace, "Expect '}' after trait body.")?;
+        Ok(self.ast.alloc_stmt(
+            Statement::Trait(Trait { name, methods }),
+            keyword.span.to(right_brace.span),
+        ))
+    }
+
+    /// Parses the name of a superclass or trait as a variable reference, failing with `msg`
+    fn class_name(&mut self, msg: &str) -> Result<ExprId, ParseError> {
+        let name = self.consume(TokenType::Identifier, msg)?;
+        let span = name.span;
+        Ok(self
+            .ast
+            .alloc_expr(Expression::Variable(Variable { name }), span))
+    }
+
+    /// funDecl -> "fun" function
+    /// method -> function | getter
+    /// function -> IDENTIFIER "(" parameters? ")" block
+    /// getter -> IDENTIFIER block
+    ///
+    /// `kind` says what's being declared, both for error messages and whether it can be a getter
+    fn function(&mut self, kind: FunctionKind) -> Result<StmtId, ParseError> {
+        // Methods that aren't static don't start with a keyword, so their span starts at the name
+        let start = match self.previous().token_type {
+            TokenType::Fun | TokenType::Class => self.previous().span,
+            _ => self.peek().span,
+        };
+        let name = self.consume(TokenType::Identifier, &format!("Expect {kind} name."))?;
+
+        let getter = kind == FunctionKind::Method && self.check(&TokenType::LeftBrace);
+        let mut params = vec![];
+        if !getter {
+            self.consume(
+                TokenType::LeftParen,
+                &format!("Expect '(' after {kind} name."),
+            )?;
+            if !self.check(&TokenType::RightParen) {
+                loop {
+                    if params.len() >= MAX_ARITY {
+                        let err = self.error(
+                            self.peek().clone(),
+                            &format!("Can't have more than {MAX_ARITY} parameters."),
+                        );
+                        self.errors.push(err);
+                    }
+                    params.push(self.consume(TokenType::Identifier, "Expect parameter name.")?);
+
+                    if !self.match_token(&[TokenType::Comma]) {
+                        break;
+                    }
+                }
+            }
+            self.consume(TokenType::RightParen, "Expect ')' after parameters.")?;
+        }
+        self.consume(
+            TokenType::LeftBrace,
+            &format!("Expect '{{' before {kind} body."),
+        )?;
+
+        // Loops outside the function don't extend into its body
+        let loop_depth = std::mem::take(&mut self.loop_depth);
+        let body = self.block();
+        self.loop_depth = loop_depth;
+        let body = body?;
+
+        let span = start.to(self.previous().span);
+        Ok(self.ast.alloc_stmt(
+            Statement::Function(Function {
+                name,
+                params,
+                body,
+                getter,
+            }),
+            span,
+        ))
+    }
+
+    /// varDecl -> "var" IDENTIFIER ( "=" expression )? ";"
+    fn var_declaration(&mut self) -> Result<StmtId, ParseError> {
+        let keyword = self.previous().clone();
+        let name = self.consume(TokenType::Identifier, "Expect variable name.")?;
+
+        let initializer = if self.match_token(&[TokenType::Equal]) {
+            Some(self.expression()?)
+        } else {
+            None
+        };
+
+        let semicolon = self.consume(
+            TokenType::Semicolon,
+            "Expect ';' after variable declaration.",
+        )?;
+        Ok(self.ast.alloc_stmt(
+            Statement::Var(Var { name, initializer }),
+            keyword.span.to(semicolon.span),
+        ))
+    }
+
+    /// statement -> printStmt | block | ifStmt | whileStmt | forStmt | breakStmt | continueStmt
+    ///            | returnStmt | exprStmt
+    fn statement(&mut self) -> Result<StmtId, ParseError> {
+        if self.match_token(&[TokenType::If]) {
+            return self.if_statement();
+        }
+        if self.match_token(&[TokenType::While]) {
+            return self.while_statement();
+        }
+        if self.match_token(&[TokenType::For]) {
+            return self.for_statement();
+        }
+        if self.match_token(&[TokenType::Break, TokenType::Continue]) {
+            return self.loop_control_statement();
+        }
+        if self.match_token(&[TokenType::Return]) {
+            return self.return_statement();
+        }
+
+        if self.match_token(&[TokenType::Print]) {
+            let keyword = self.previous().clone();
+            let value = self.expression()?;
+            let semicolon = self.consume(TokenType::Semicolon, "Expect ';' after value.")?;
+            let span = keyword.span.to(semicolon.span);
+            return Ok(self
+                .ast
+                .alloc_stmt(Statement::Print(Print { keyword, value }), span));
+        }
+
+        if self.match_token(&[TokenType::LeftBrace]) {
+            let left_brace = self.previous().clone();
+            let statements = self.block()?;
+            let span = left_brace.span.to(self.previous().span);
+            return Ok(self.ast.alloc_stmt(Statement::Block(statements), span));
+        }
+
+        let expr = self.expression()?;
+        let semicolon = self.consume(TokenType::Semicolon, "Expect ';' after expression.")?;
+        let span = self.ast.expr_span(expr).to(semicolon.span);
+        Ok(self.ast.alloc_stmt(Statement::Expression(expr), span))
+    }
+
+    /// ifStmt -> "if" "(" expression ")" statement ( "else" statement )?
+    fn if_statement(&mut self) -> Result<StmtId, ParseError> {
+        let keyword = self.previous().clone();
+        self.consume(TokenType::LeftParen, "Expect '(' after 'if'.")?;
+        let condition = self.expression()?;
+        self.consume(TokenType::RightParen, "Expect ')' after if condition.")?;
+
+        let then_branch = self.statement()?;
+        let else_branch = if self.match_token(&[TokenType::Else]) {
+            Some(self.statement()?)
+        } else {
+            None
+        };
+
+        let span = keyword
+            .span
+            .to(self.ast.stmt_span(else_branch.unwrap_or(then_branch)));
+        Ok(self.ast.alloc_stmt(
+            Statement::If(If {
+                condition,
+                then_branch,
+                else_branch,
+            }),
+            span,
+        ))
+    }
+
+    /// whileStmt -> "while" "(" expression ")" statement
+    fn while_statement(&mut self) -> Result<StmtId, ParseError> {
+        let keyword = self.previous().clone();
+        self.consume(TokenType::LeftParen, "Expect '(' after 'while'.")?;
+        let condition = self.expression()?;
+        self.consume(TokenType::RightParen, "Expect ')' after condition.")?;
+        let body = self.loop_body()?;
+
+        let span = keyword.span.to(self.ast.stmt_span(body));
+        Ok(self.ast.alloc_stmt(
+            Statement::While(While {
+                condition,
+                body,
+                increment: None,
+            }),
+            span,
+        ))
+    }
+
+    /// forStmt -> "for" "(" ( varDecl | exprStmt | ";" ) expression? ";" expression? ")" statement
+    ///          | "for" "(" "var"? IDENTIFIER "in" expression ")" statement
+    ///
+    /// The C-style form is desugared into a `while` loop, wrapped in a block when there's an
+    /// initializer
+    fn for_statement(&mut self) -> Result<StmtId, ParseError> {
+        let keyword = self.previous().clone();
+        self.consume(TokenType::LeftParen, "Expect '(' after 'for'.")?;
+
+        let is_for_in = match self.peek().token_type {
+            TokenType::Identifier => self.check_ahead(1, &TokenType::In),
+            TokenType::Var => {
+                self.check_ahead(1, &TokenType::Identifier) && self.check_ahead(2, &TokenType::In)
+            }
+            _ => false,
+        };
+        if is_for_in {
+            return self.for_in_statement(keyword);
+        }
+
+        let initializer = if self.match_token(&[TokenType::Semicolon]) {
+            None
+        } else if self.match_token(&[TokenType::Var]) {
+            Some(self.var_declaration()?)
+        } else {
+            let expr = self.expression()?;
+            let semicolon = self.consume(TokenType::Semicolon, "Expect ';' after expression.")?;
+            let span = self.ast.expr_span(expr).to(semicolon.span);
+            Some(self.ast.alloc_stmt(Statement::Expression(expr), span))
+        };
+
+        let condition = if self.check(&TokenType::Semicolon) {
+            // A missing condition loops forever
+            let span = self.peek().span;
+            self.ast
+                .alloc_expr(Expression::Literal(Literal::Boolean(true)), span)
+        } else {
+            self.expression()?
+        };
+        self.consume(TokenType::Semicolon, "Expect ';' after loop condition.")?;
+
+        let increment = if self.check(&TokenType::RightParen) {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        self.consume(TokenType::RightParen, "Expect ')' after for clauses.")?;
+
+        let body = self.loop_body()?;
+        let span = keyword.span.to(self.ast.stmt_span(body));
+        let while_loop = self.ast.alloc_stmt(
+            Statement::While(While {
+                condition,
+                body,
+                increment,
+            }),
+            span,
+        );
+
+        Ok(match initializer {
+            Some(initializer) => self
+                .ast
+                .alloc_stmt(Statement::Block(vec![initializer, while_loop]), span),
+            None => while_loop,
+        })
+    }
+
+    /// The rest of a `for-in` loop, after `for (`
+    fn for_in_statement(&mut self, keyword: Token) -> Result<StmtId, ParseError> {
+        self.match_token(&[TokenType::Var]);
+        let name = self.consume(TokenType::Identifier, "Expect loop variable name.")?;
+        let in_keyword = self.consume(TokenType::In, "Expect 'in' after loop variable.")?;
+        let iterable = self.expression()?;
+        self.consume(TokenType::RightParen, "Expect ')' after for-in clause.")?;
+        let body = self.loop_body()?;
+
+        let span = keyword.span.to(self.ast.stmt_span(body));
+        Ok(self.ast.alloc_stmt(
+            Statement::ForIn(ForIn {
+                name,
+                keyword: in_keyword,
+                iterable,
+                body,
+            }),
+            span,
+        ))
+    }
+
+    /// Parses the body of a loop, inside which `break` and `continue` are allowed
+    fn loop_body(&mut self) -> Result<StmtId, ParseError> {
+        self.loop_depth += 1;
+        let body = self.statement();
+        self.loop_depth -= 1;
+        body
+    }
+
+    /// breakStmt -> "break" ";"
+    /// continueStmt -> "continue" ";"
+    fn loop_control_statement(&mut self) -> Result<StmtId, ParseError> {
+        let keyword = self.previous().clone();
+        if self.loop_depth == 0 {
+            let msg = format!("Can't use '{}' outside of a loop.", keyword.lexeme);
+            let err = self.error(keyword.clone(), &msg);
+            self.errors.push(err);
+        }
+
+        let semicolon = self.consume(
+            TokenType::Semicolon,
+            &format!("Expect ';' after '{}'.", keyword.lexeme),
+        )?;
+        let span = keyword.span.to(semicolon.span);
+        let stmt = match keyword.token_type {
+            TokenType::Break => Statement::Break,
+            _ => Statement::Continue,
+        };
+        Ok(self.ast.alloc_stmt(stmt, span))
+    }
+
+    /// returnStmt -> "return" expression? ";"
+    fn return_statement(&mut self) -> Result<StmtId, ParseError> {
+        let keyword = self.previous().clone();
+        let value = if self.check(&TokenType::Semicolon) {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+
+        let semicolon = self.consume(TokenType::Semicolon, "Expect ';' after return value.")?;
+        let span = keyword.span.to(semicolon.span);
+        Ok(self
+            .ast
+            .alloc_stmt(Statement::Return(Return { keyword, value }), span))
+    }
+
+    /// block -> "{" declaration* "}"
+    fn block(&mut self) -> Result<Vec<StmtId>, ParseError> {
+        let mut statements = vec![];
+        while !self.check(&TokenType::RightBrace) && !self.is_at_end() {
+            statements.push(self.declaration()?);
+        }
+
+        self.consume(TokenType::RightBrace, "Expect '}' after block.")?;
+        Ok(statements)
+    }
+
+    /// Parses a full expression, including the comma operator
+    fn expression(&mut self) -> Result<ExprId, ParseError> {
+        self.parse_precedence(Precedence::Comma)
+    }
+
+    /// Parses an expression whose operators all bind at least as tightly as `precedence`
+    fn parse_precedence(&mut self, precedence: Precedence) -> Result<ExprId, ParseError> {
+        let token = self.peek().clone();
+        let Some(prefix) = Self::rule(&token.token_type).prefix else {
+            return Err(self.error(token, "Expect expression."));
+        };
+        self.advance();
+        let mut expr = prefix(self)?;
+
+        while precedence <= Self::rule(&self.peek().token_type).precedence {
+            self.advance();
+            let infix = Self::rule(&self.previous().token_type)
+                .infix
+                .expect("tokens with a precedence have an infix rule");
+            expr = infix(self, expr)?;
+        }
+
+        Ok(expr)
+    }
+
+    /// The parse rules for a token type. Adding an operator only needs an entry here.
+    fn rule(token_type: &TokenType) -> ParseRule<'a> {
+        use Associativity::*;
+        use Precedence as P;
+
+        let (prefix, infix, precedence, associativity): (
+            Option<PrefixFn<'a>>,
+            Option<InfixFn<'a>>,
+            Precedence,
+            Associativity,
+        ) = match token_type {
+            TokenType::LeftParen => (Some(Self::grouping), Some(Self::call), P::Call, Left),
+            TokenType::Dot => (None, Some(Self::get), P::Call, Left),
+            TokenType::LeftBracket => (Some(Self::list), Some(Self::index), P::Call, Left),
+            // Only reached in expressions, since a statement starting with `{` is a block
+            TokenType::LeftBrace => (Some(Self::map), None, P::None, Left),
+            TokenType::Comma => (None, Some(Self::comma), P::Comma, Left),
+            TokenType::Equal => (None, Some(Self::assign), P::Assignment, Right),
+            TokenType::PlusEqual
+            | TokenType::MinusEqual
+            | TokenType::StarEqual
+            | TokenType::SlashEqual
+            | TokenType::PercentEqual => (None, Some(Self::compound_assign), P::Assignment, Right),
+            // Binds as tightly as a call as a suffix, so `-a++` is `-(a++)`
+            TokenType::PlusPlus | TokenType::MinusMinus => (
+                Some(Self::prefix_update),
+                Some(Self::postfix_update),
+                P::Call,
+                Left,
+            ),
+            TokenType::Question => (None, Some(Self::conditional), P::Conditional, Right),
+            TokenType::Or => (
+                Some(Self::missing_left_operand),
+                Some(Self::logical),
+                P::Or,
+                Left,
+            ),
+            TokenType::And => (
+                Some(Self::missing_left_operand),
+                Some(Self::logical),
+                P::And,
+                Left,
+            ),
+            TokenType::BangEqual | TokenType::EqualEqual => (
+                Some(Self::missing_left_operand),
+                Some(Self::binary),
+                P::Equality,
+                Left,
+            ),
+            TokenType::Greater
+            | TokenType::GreaterEqual
+            | TokenType::Less
+            | TokenType::LessEqual => (
+                Some(Self::missing_left_operand),
+                Some(Self::binary),
+                P::Comparison,
+                Left,
+            ),
+            TokenType::In => (
+                Some(Self::missing_left_operand),
+                Some(Self::binary),
+                P::Comparison,
+                Left,
+            ),
+            TokenType::DotDot | TokenType::DotDotEqual => (
+                Some(Self::missing_left_operand),
+                Some(Self::binary),
+                P::Range,
+                Left,
+            ),
+            TokenType::Minus => (Some(Self::unary), Some(Self::binary), P::Term, Left),
+            TokenType::Plus => (
+                Some(Self::missing_left_operand),
+                Some(Self::binary),
+                P::Term,
+                Left,
+            ),
+            TokenType::Slash | TokenType::Star | TokenType::Percent | TokenType::TildeSlash => (
+                Some(Self::missing_left_operand),
+                Some(Self::binary),
+                P::Factor,
+                Left,
+            ),
+            // Binds tighter than unary operators, so `-2 ** 2` is `-(2 ** 2)`
+            TokenType::StarStar => (
+                Some(Self::missing_left_operand),
+                Some(Self::binary),
+                P::Exponent,
+                Right,
+            ),
+            TokenType::Bang => (Some(Self::unary), None, P::None, Left),
+            TokenType::Number
+            | TokenType::String
+            | TokenType::True
+            | TokenType::False
+            | TokenType::Nil => (Some(Self::literal), None, P::None, Left),
+            TokenType::Identifier => (Some(Self::variable), None, P::None, Left),
+            TokenType::This => (Some(Self::this), None, P::None, Left),
+            TokenType::Super => (Some(Self::super_), None, P::None, Left),
+            _ => (None, None, P::None, Left),
+        };
+
+        ParseRule {
+            prefix,
+            infix,
+            precedence,
+            associativity,
+        }
+    }
+
+    /// The precedence to parse the right operand of `operator` at
+    fn right_precedence(operator: &TokenType) -> Precedence {
+        let rule = Self::rule(operator);
+        match rule.associativity {
+            Associativity::Left => rule.precedence.next(),
+            Associativity::Right => rule.precedence,
+        }
+    }
+
+    /// NUMBER | STRING | "true" | "false" | "nil"
+    fn literal(&mut self) -> Result<ExprId, ParseError> {
+        let token = self.previous().clone();
+        let literal = match token.token_type {
+            TokenType::False => Literal::Boolean(false),
+            TokenType::True => Literal::Boolean(true),
+            TokenType::Nil => Literal::Nil,
+            _ => match token.literal {
+                TokenLiteral::Num(n) => Literal::Number(n),
+                TokenLiteral::Str(s) => Literal::String(s),
+                TokenLiteral::None => unreachable!("scanner always attaches a literal"),
+            },
+        };
+
+        Ok(self
+            .ast
+            .alloc_expr(Expression::Literal(literal), token.span))
+    }
+
+    /// IDENTIFIER
+    fn variable(&mut self) -> Result<ExprId, ParseError> {
+        let name = self.previous().clone();
+        let span = name.span;
+        Ok(self
+            .ast
+            .alloc_expr(Expression::Variable(Variable { name }), span))
+    }
+
+    /// "this"
+    fn this(&mut self) -> Result<ExprId, ParseError> {
+        let keyword = self.previous().clone();
+        let span = keyword.span;
+        Ok(self
+            .ast
+            .alloc_expr(Expression::This(This { keyword }), span))
+    }
+
+    /// "[" ( expression ( "," expression )* ","? )? "]"
+    fn list(&mut self) -> Result<ExprId, ParseError> {
+        let left_bracket = self.previous().clone();
+        let mut elements = vec![];
+        while !self.check(&TokenType::RightBracket) {
+            // Commas separate the elements, so they can't be comma expressions themselves
+            elements.push(self.parse_precedence(Precedence::Assignment)?);
+            if !self.match_token(&[TokenType::Comma]) {
+                break;
+            }
+        }
+
+        let right_bracket =
+            self.consume(TokenType::RightBracket, "Expect ']' after list elements.")?;
+        let span = left_bracket.span.to(right_bracket.span);
+        Ok(self
+            .ast
+            .alloc_expr(Expression::List(List { elements }), span))
+    }
+
+    /// "{" ( entry ( "," entry )* ","? )? "}"
+    /// entry -> expression ":" expression
+    fn map(&mut self) -> Result<ExprId, ParseError> {
+        let left_brace = self.previous().clone();
+        let mut entries = vec![];
+        while !self.check(&TokenType::RightBrace) {
+            // Commas separate the entries, so they can't be comma expressions themselves
+            let key = self.parse_precedence(Precedence::Assignment)?;
+            self.consume(TokenType::Colon, "Expect ':' after map key.")?;
+            let value = self.parse_precedence(Precedence::Assignment)?;
+            entries.push((key, value));
+            if !self.match_token(&[TokenType::Comma]) {
+                break;
+            }
+        }
+
+        let right_brace = self.consume(TokenType::RightBrace, "Expect '}' after map entries.")?;
+        let span = left_brace.span.to(right_brace.span);
+        Ok(self.ast.alloc_expr(
+            Expression::Map(Map {
+                brace: left_brace,
+                entries,
+            }),
+            span,
+        ))
+    }
+
+    /// "super" "." IDENTIFIER
+    fn super_(&mut self) -> Result<ExprId, ParseError> {
+        let keyword = self.previous().clone();
+        self.consume(TokenType::Dot, "Expect '.' after 'super'.")?;
+        let method = self.consume(TokenType::Identifier, "Expect superclass method name.")?;
+        let span = keyword.span.to(method.span);
+        Ok(self
+            .ast
+            .alloc_expr(Expression::Super(Super { keyword, method }), span))
+    }
+
+    /// "(" expression ")"
+    fn grouping(&mut self) -> Result<ExprId, ParseError> {
+        let left_paren = self.previous().clone();
+        let expr = self.expression()?;
+        let right_paren = self.consume(TokenType::RightParen, "Expect ')' after expression.")?;
+        let span = left_paren.span.to(right_paren.span);
+        Ok(self
+            .ast
+            .alloc_expr(Expression::Grouping(Grouping(expr)), span))
+    }
+
+    /// ( "!" | "-" ) expression
+    fn unary(&mut self) -> Result<ExprId, ParseError> {
+        let operator = self.previous().clone();
+        let right = self.parse_precedence(Precedence::Unary)?;
+        let span = operator.span.to(self.ast.expr_span(right));
+        Ok(self
+            .ast
+            .alloc_expr(Expression::Unary(Unary { operator, right }), span))
+    }
+
+    /// Error production for a binary operator in prefix position, e.g. `+ 3`. The right operand
+    /// is parsed at the operator's own precedence so that parsing carries on where it would have
+    /// if the left operand were there.
+    fn missing_left_operand(&mut self) -> Result<ExprId, ParseError> {
+        let operator = self.previous().clone();
+        let msg = format!(
+            "binary operator '{}' requires a left operand",
+            operator.lexeme
+        );
+        let err = self.error(operator.clone(), &msg);
+        self.errors.push(err);
+
+        // The right operand is discarded since the expression can never be run
+        self.parse_precedence(Self::right_precedence(&operator.token_type))
+    }
+
+    /// expression OPERATOR expression
+    fn binary(&mut self, left: ExprId) -> Result<ExprId, ParseError> {
+        let operator = self.previous().clone();
+        let right = self.parse_precedence(Self::right_precedence(&operator.token_type))?;
+        Ok(self.alloc_binary(left, operator, right))
+    }
+
+    /// expression ( "and" | "or" ) expression
+    fn logical(&mut self, left: ExprId) -> Result<ExprId, ParseError> {
+        let operator = self.previous().clone();
+        let right = self.parse_precedence(Self::right_precedence(&operator.token_type))?;
+        let span = self.ast.expr_span(left).to(self.ast.expr_span(right));
+        Ok(self.ast.alloc_expr(
+            Expression::Logical(Logical {
+                left,
+                operator,
+                right,
+            }),
+            span,
+        ))
+    }
+
+    /// expression "(" arguments? ")"
+    fn call(&mut self, callee: ExprId) -> Result<ExprId, ParseError> {
+        let mut arguments = vec![];
+        if !self.check(&TokenType::RightParen) {
+            loop {
+                if arguments.len() >= MAX_ARITY {
+                    let err = self.error(
+                        self.peek().clone(),
+                        &format!("Can't have more than {MAX_ARITY} arguments."),
+                    );
+                    self.errors.push(err);
+                }
+                // Commas separate the arguments, so they can't be comma expressions themselves
+                arguments.push(self.parse_precedence(Precedence::Assignment)?);
+
+                if !self.match_token(&[TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+
+        let paren = self.consume(TokenType::RightParen, "Expect ')' after arguments.")?;
+        let span = self.ast.expr_span(callee).to(paren.span);
+        Ok(self.ast.alloc_expr(
+            Expression::Call(Call {
+                callee,
+                paren,
+                arguments,
+            }),
+            span,
+        ))
+    }
+
+    /// expression "." IDENTIFIER
+    fn get(&mut self, object: ExprId) -> Result<ExprId, ParseError> {
+        let name = self.consume(TokenType::Identifier, "Expect property name after '.'.")?;
+        let span = self.ast.expr_span(object).to(name.span);
+        Ok(self
+            .ast
+            .alloc_expr(Expression::Get(Get { object, name }), span))
+    }
+
+    /// expression "[" ( expression | expression? ":" expression? ) "]"
+    fn index(&mut self, object: ExprId) -> Result<ExprId, ParseError> {
+        let mut bracket = self.previous().clone();
+
+        let start = if self.check(&TokenType::Colon) {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        let subscript = match start {
+            Some(index) if !self.check(&TokenType::Colon) => Subscript::Index(index),
+            _ => {
+                self.consume(TokenType::Colon, "Expect ':' in slice.")?;
+                let end = if self.check(&TokenType::RightBracket) {
+                    None
+                } else {
+                    Some(self.expression()?)
+                };
+                Subscript::Slice(start, end)
+            }
+        };
+
+        let right_bracket = self.consume(TokenType::RightBracket, "Expect ']' after index.")?;
+        bracket.span = bracket.span.to(right_bracket.span);
+        let span = self.ast.expr_span(object).to(right_bracket.span);
+        Ok(self.ast.alloc_expr(
+            Expression::Index(Index {
+                object,
+                bracket,
+                subscript,
+            }),
+            span,
+        ))
+    }
+
+    /// ( IDENTIFIER | expression "." IDENTIFIER | expression "[" expression "]" ) "=" expression
+    fn assign(&mut self, target: ExprId) -> Result<ExprId, ParseError> {
+        let equals = self.previous().clone();
+        let value = self.parse_precedence(Self::right_precedence(&TokenType::Equal))?;
+
+        let span = self.ast.expr_span(target).to(self.ast.expr_span(value));
+        let expr = match self.ast.expr(target) {
+            Expression::Variable(variable) => Expression::Assign(Assign {
+                name: variable.name.clone(),
+                value,
+            }),
+            Expression::Get(get) => Expression::Set(Set {
+                object: get.object,
+                name: get.name.clone(),
+                value,
+            }),
+            Expression::Index(Index {
+                object,
+                bracket,
+                subscript: Subscript::Index(index),
+            }) => Expression::SetIndex(SetIndex {
+                object: *object,
+                bracket: bracket.clone(),
+                index: *index,
+                value,
+            }),
+            _ => {
+                // There's no need to unwind since the parser isn't confused about where it is
+                let err = self.error(equals, "Invalid assignment target.");
+                self.errors.push(err);
+                return Ok(target);
+            }
+        };
+        Ok(self.ast.alloc_expr(expr, span))
+    }
+
+    /// target ( "+=" | "-=" | "*=" | "/=" | "%=" ) expression
+    fn compound_assign(&mut self, target: ExprId) -> Result<ExprId, ParseError> {
+        let operator = self.previous().clone();
+        let value = self.parse_precedence(Self::right_precedence(&operator.token_type))?;
+        let span = self.ast.expr_span(target).to(self.ast.expr_span(value));
+        Ok(self.update(target, operator, Some(value), false, span))
+    }
+
+    /// ( "++" | "--" ) target
+    fn prefix_update(&mut self) -> Result<ExprId, ParseError> {
+        let operator = self.previous().clone();
+        // Only calls and what they're made of, so that `++a ** 2` is `(++a) ** 2`
+        let target = self.parse_precedence(Precedence::Call)?;
+        let span = operator.span.to(self.ast.expr_span(target));
+        Ok(self.update(target, operator, None, false, span))
+    }
+
+    /// target ( "++" | "--" )
+    fn postfix_update(&mut self, target: ExprId) -> Result<ExprId, ParseError> {
+        let operator = self.previous().clone();
+        let span = self.ast.expr_span(target).to(operator.span);
+        Ok(self.update(target, operator, None, true, span))
+    }
+
+    /// Allocates an `Update` of `target`, if it's something that can be assigned to
+    fn update(
+        &mut self,
+        target: ExprId,
+        operator: Token,
+        value: Option<ExprId>,
+        postfix: bool,
+        span: Span,
+    ) -> ExprId {
+        let assignable = matches!(
+            self.ast.expr(target),
+            Expression::Variable(_)
+                | Expression::Get(_)
+                | Expression::Index(Index {
+                    subscript: Subscript::Index(_),
+                    ..
+                })
+        );
+        if !assignable {
+            // Like `assign`, there's no need to unwind
+            let err = self.error(operator, "Invalid assignment target.");
+            self.errors.push(err);
+            return target;
+        }
+
+        let update = Update {
+            target,
+            operator,
+            value,
+            postfix,
+        };
+        self.ast.alloc_expr(Expression::Update(update), span)
+    }
+
+    /// expression "," expression
+    fn comma(&mut self, left: ExprId) -> Result<ExprId, ParseError> {
+        let right = self.parse_precedence(Self::right_precedence(&TokenType::Comma))?;
+        let span = self.ast.expr_span(left).to(self.ast.expr_span(right));
+        Ok(self
+            .ast
+            .alloc_expr(Expression::Comma(Comma { left, right }), span))
+    }
+
+    /// expression "?" expression ":" expression
+    fn conditional(&mut self, condition: ExprId) -> Result<ExprId, ParseError> {
+        // Anything may appear between `?` and `:` since the two delimit it
+        let then_branch = self.expression()?;
+        self.consume(
+            TokenType::Colon,
+            "Expect ':' after then branch of conditional.",
+        )?;
+        let else_branch = self.parse_precedence(Self::right_precedence(&TokenType::Question))?;
+        let span = self
+            .ast
+            .expr_span(condition)
+            .to(self.ast.expr_span(else_branch));
+        Ok(self.ast.alloc_expr(
+            Expression::Conditional(Conditional {
+                condition,
+                then_branch,
+                else_branch,
+            }),
+            span,
+        ))
+    }
+
+    /// Allocates a binary node spanning both operands
+    fn alloc_binary(&mut self, left: ExprId, operator: Token, right: ExprId) -> ExprId {
+        let span = self.ast.expr_span(left).to(self.ast.expr_span(right));
+        self.ast.alloc_expr(
+            Expression::Binary(Binary {
+                left,
+                operator,
+                right,
+            }),
+            span,
+        )
+    }
+
+    /// Advances past the current token if it matches any of `types`
+    fn match_token(&mut self, types: &[TokenType]) -> bool {
+        if types.iter().any(|t| self.check(t)) {
+            self.advance();
+            return true;
+        }
+        false
+    }
+
+    /// Advances past a token of type `token_type`, or fails with `msg`
+    fn consume(&mut self, token_type: TokenType, msg: &str) -> Result<Token, ParseError> {
+        if self.check(&token_type) {
+            return Ok(self.advance().clone());
+        }
+        Err(self.error(self.peek().clone(), msg))
+    }
+
+    fn check(&self, token_type: &TokenType) -> bool {
+        !self.is_at_end() && &self.peek().token_type == token_type
+    }
+
+    fn advance(&mut self) -> &Token {
+        if !self.is_at_end() {
+            self.current += 1;
+        }
+        self.previous()
+    }
+
+    /// Whether the token `distance` past the current one is a `token_type`
+    fn check_ahead(&self, distance: usize, token_type: &TokenType) -> bool {
+        self.tokens
+            .get(self.current + distance)
+            .is_some_and(|token| &token.token_type == token_type)
+    }
+
+    fn is_at_end(&self) -> bool {
+        self.peek().token_type == TokenType::EOF
+    }
+
+    fn peek(&self) -> &Token {
+        &self.tokens[self.current]
+    }
+
+    fn previous(&self) -> &Token {
+        &self.tokens[self.current - 1]
+    }
+
+    /// Discards tokens until the start of what's probably the next statement
+    fn synchronize(&mut self) {
+        self.advance();
+
+        while !self.is_at_end() {
+            if self.previous().token_type == TokenType::Semicolon {
+                return;
+            }
+
+            match self.peek().token_type {
+                TokenType::Class
+                | TokenType::Trait
+                | TokenType::Fun
+                | TokenType::Var
+                | TokenType::For
+                | TokenType::If
+                | TokenType::While
+                | TokenType::Print
+                | TokenType::Return => return,
+                _ => {
+                    self.advance();
+                }
+            }
+        }
+    }
+
+    /// Builds an error at `token`, which the caller either returns to unwind or records
+    fn error(&self, token: Token, msg: &str) -> ParseError {
+        ParseError {
+            token,
+            message: msg.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::Scanner;
+
+    /// The book's one-function-per-level grammar, kept as a reference for the Pratt parser
+    impl Parser<'_> {
+        /// expression -> comma
+        fn classic_expression(&mut self) -> Result<ExprId, ParseError> {
+            self.classic_comma()
+        }
+
+        /// comma -> conditional ( "," conditional )*
+        fn classic_comma(&mut self) -> Result<ExprId, ParseError> {
+            let mut expr = self.classic_conditional()?;
+
+            while self.match_token(&[TokenType::Comma]) {
+                let right = self.classic_conditional()?;
+                let span = self.ast.expr_span(expr).to(self.ast.expr_span(right));
+                expr = self
+                    .ast
+                    .alloc_expr(Expression::Comma(Comma { left: expr, right }), span);
+            }
+
+            Ok(expr)
+        }
+
+        /// conditional -> equality ( "?" expression ":" conditional )?
+        fn classic_conditional(&mut self) -> Result<ExprId, ParseError> {
+            let condition = self.classic_equality()?;
+
+            if self.match_token(&[TokenType::Question]) {
+                let then_branch = self.classic_expression()?;
+                self.consume(
+                    TokenType::Colon,
+                    "Expect ':' after then branch of conditional.",
+                )?;
+                // Recursing at the same level makes `a ? b : c ? d : e` group to the right
+                let else_branch = self.classic_conditional()?;
+                let span = self
+                    .ast
+                    .expr_span(condition)
+                    .to(self.ast.expr_span(else_branch));
+                return Ok(self.ast.alloc_expr(
+                    Expression::Conditional(Conditional {
+                        condition,
+                        then_branch,
+                        else_branch,
+                    }),
+                    span,
+                ));
+            }
+
+            Ok(condition)
+        }
+
+        /// equality -> comparison ( ( "!=" | "==" ) comparison )*
+        fn classic_equality(&mut self) -> Result<ExprId, ParseError> {
+            let mut expr = self.classic_comparison()?;
+
+            while self.match_token(&[TokenType::BangEqual, TokenType::EqualEqual]) {
+                let operator = self.previous().clone();
+                let right = self.classic_comparison()?;
+                expr = self.alloc_binary(expr, operator, right);
+            }
+
+            Ok(expr)
+        }
+
+        /// comparison -> term ( ( ">" | ">=" | "<" | "<=" ) term )*
+        fn classic_comparison(&mut self) -> Result<ExprId, ParseError> {
+            let mut expr = self.classic_term()?;
+
+            while self.match_token(&[
+                TokenType::Greater,
+                TokenType::GreaterEqual,
+                TokenType::Less,
+                TokenType::LessEqual,
+            ]) {
+                let operator = self.previous().clone();
+                let right = self.classic_term()?;
+                expr = self.alloc_binary(expr, operator, right);
+            }
+
+            Ok(expr)
+        }
+
+        /// term -> factor ( ( "-" | "+" ) factor )*
+        fn classic_term(&mut self) -> Result<ExprId, ParseError> {
+            let mut expr = self.classic_factor()?;
+
+            while self.match_token(&[TokenType::Minus, TokenType::Plus]) {
+                let operator = self.previous().clone();
+                let right = self.classic_factor()?;
+                expr = self.alloc_binary(expr, operator, right);
+            }
+
+            Ok(expr)
+        }
+
+        /// factor -> unary ( ( "/" | "*" ) unary )*
+        fn classic_factor(&mut self) -> Result<ExprId, ParseError> {
+            let mut expr = self.classic_unary()?;
+
+            while self.match_token(&[TokenType::Slash, TokenType::Star]) {
+                let operator = self.previous().clone();
+                let right = self.classic_unary()?;
+                expr = self.alloc_binary(expr, operator, right);
+            }
+
+            Ok(expr)
+        }
+
+        /// unary -> ( "!" | "-" ) unary | primary
+        fn classic_unary(&mut self) -> Result<ExprId, ParseError> {
+            if self.match_token(&[TokenType::Bang, TokenType::Minus]) {
+                let operator = self.previous().clone();
+                let right = self.classic_unary()?;
+                let span = operator.span.to(self.ast.expr_span(right));
+                return Ok(self
+                    .ast
+                    .alloc_expr(Expression::Unary(Unary { operator, right }), span));
+            }
+
+            self.classic_primary()
+        }
+
+        /// primary -> NUMBER | STRING | "true" | "false" | "nil" | "(" expression ")"
+        fn classic_primary(&mut self) -> Result<ExprId, ParseError> {
+            let token = self.peek().clone();
+            let literal = match token.token_type {
+                TokenType::False => Literal::Boolean(false),
+                TokenType::True => Literal::Boolean(true),
+                TokenType::Nil => Literal::Nil,
+                TokenType::Number | TokenType::String => match token.literal {
+                    TokenLiteral::Num(n) => Literal::Number(n),
+                    TokenLiteral::Str(ref s) => Literal::String(s.to_owned()),
+                    TokenLiteral::None => unreachable!("scanner always attaches a literal"),
+                },
+                TokenType::LeftParen => {
+                    self.advance();
+                    let expr = self.classic_expression()?;
+                    let right_paren =
+                        self.consume(TokenType::RightParen, "Expect ')' after expression.")?;
+                    let span = token.span.to(right_paren.span);
+                    return Ok(self
+                        .ast
+                        .alloc_expr(Expression::Grouping(Grouping(expr)), span));
+                }
+                _ => return Err(self.error(token, "Expect expression.")),
+            };
+            self.advance();
+
+            Ok(self
+                .ast
+                .alloc_expr(Expression::Literal(literal), token.span))
+        }
+    }
+
+    fn parse_classic(source: &str) -> String {
+        let tokens = Scanner::new(source.to_string()).scan_tokens().unwrap();
+        let mut ast = Ast::new();
+        let expr = Parser::new(tokens, &mut ast).classic_expression().unwrap();
+        format!("{}", ast.display(expr))
+    }
+
+    fn parse(source: &str) -> String {
+        let tokens = Scanner::new(source.to_string()).scan_tokens().unwrap();
+        let mut ast = Ast::new();
+        let expr = Parser::new(tokens, &mut ast).parse_expression().unwrap();
+        format!("{}", ast.display(expr))
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!("(+ 1 (* 2 3))", parse("1 + 2 * 3"));
+        assert_eq!("(* (group (+ 1 2)) 3)", parse("(1 + 2) * 3"));
+        assert_eq!("(== (< 1 2) (! false))", parse("1 < 2 == !false"));
+        assert_eq!("(- (- 1))", parse("- -1"));
+    }
+
+    #[test]
+    fn conditional() {
+        assert_eq!("(?: true 1 2)", parse("true ? 1 : 2"));
+        assert_eq!(
+            "(?: true (?: false 1 2) 3)",
+            parse("true ? false ? 1 : 2 : 3")
+        );
+        assert_eq!("(?: 1 2 (?: 3 4 5))", parse("1 ? 2 : 3 ? 4 : 5"));
+        assert_eq!("(?: (== 1 2) (+ 1 2) 3)", parse("1 == 2 ? 1 + 2 : 3"));
+    }
+
+    #[test]
+    fn comma() {
+        assert_eq!("(, (, 1 2) 3)", parse("1, 2, 3"));
+        assert_eq!("(, (?: true 1 2) 3)", parse("true ? 1 : 2, 3"));
+        assert_eq!("(?: true (, 1 2) 3)", parse("true ? 1, 2 : 3"));
+    }
+
+    #[test]
+    fn spans() {
+        let source = "1 + (2 * 3)";
+        let tokens = Scanner::new(source.to_string()).scan_tokens().unwrap();
+        let mut ast = Ast::new();
+        let expr = Parser::new(tokens, &mut ast).parse_expression().unwrap();
+        assert_eq!(source, ast.expr_span(expr).slice(source));
+
+        let Expression::Binary(binary) = ast.expr(expr) else {
+            panic!("expected a binary expression");
+        };
+        assert_eq!("(2 * 3)", ast.expr_span(binary.right).slice(source));
+    }
+
+    #[test]
+    fn errors() {
+        let tokens = Scanner::new("1 ? 2".to_string()).scan_tokens().unwrap();
+        let mut ast = Ast::new();
+        let errors = Parser::new(tokens, &mut ast)
+            .parse_expression()
+            .unwrap_err();
+        assert_eq!(1, errors.len());
+        assert_eq!(
+            "Expect ':' after then branch of conditional.",
+            errors[0].message
+        );
+    }
+
+    #[test]
+    fn missing_left_operand() {
+        let messages = |source: &str| {
+            let tokens = Scanner::new(source.to_string()).scan_tokens().unwrap();
+            let mut ast = Ast::new();
+            Parser::new(tokens, &mut ast)
+                .parse_expression()
+                .unwrap_err()
+                .into_iter()
+                .map(|e| e.message)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            vec!["binary operator '+' requires a left operand"],
+            messages("+ 3")
+        );
+        assert_eq!(
+            vec!["binary operator '==' requires a left operand"],
+            messages("== 1 < 2")
+        );
+        // Parsing continues after the error production, so later errors are still found
+        assert_eq!(
+            vec![
+                "binary operator '*' requires a left operand",
+                "binary operator '/' requires a left operand",
+                "Expect expression."
+            ],
+            messages("(* 2 + 1) == (/ 4) + )")
+        );
+    }
+
+    #[test]
+    fn missing_left_operand_precedence() {
+        // `* 2` binds the `2` alone, leaving `+ 3` to apply to the discarded operand
+        let tokens = Scanner::new("* 2 + 3".to_string()).scan_tokens().unwrap();
+        let mut ast = Ast::new();
+        let mut parser = Parser::new(tokens, &mut ast);
+        let expr = parser.expression().unwrap();
+        assert_eq!("(+ 2 3)", format!("{}", ast.display(expr)));
+    }
+
+    #[test]
+    fn matches_classic_grammar() {
+        let sources = [
+            "1 + 2 * 3 - 4 / 5",
+            "1 - 2 - 3",
+            "-1 * -2",
+            "!!true == false",
+            "1 < 2 == 3 >= 4",
+            "(1 + 2) * (3 - 4) / 5",
+            "1 == 2 != 3 == 4",
+            "true ? 1 + 2 : 3 * 4",
+            "1 ? 2 : 3 ? 4 : 5",
+            "1 ? 2 ? 3 : 4 : 5",
+            "1, 2 ? 3, 4 : 5, 6",
+            "\"a\" + \"b\" == \"ab\" ? nil : -(1 - -2)",
+        ];
+
+        for source in sources {
+            assert_eq!(parse_classic(source), parse(source), "{source}");
+        }
+    }
+
+    #[test]
+    fn assignment() {
+        assert_eq!("(= a (= b 1))", parse("a = b = 1"));
+        assert_eq!("(, (= a 1) (= b 2))", parse("a = 1, b = 2"));
+        assert_eq!("(= a (?: c 1 2))", parse("a = c ? 1 : 2"));
+
+        let tokens = Scanner::new("a + b = c;".to_string())
+            .scan_tokens()
+            .unwrap();
+        let mut ast = Ast::new();
+        let errors = Parser::new(tokens, &mut ast).parse().unwrap_err();
+        assert_eq!(1, errors.len());
+        assert_eq!("Invalid assignment target.", errors[0].message);
+        assert_eq!(TokenType::Equal, errors[0].token.token_type);
+    }
+
+    #[test]
+    fn updates() {
+        assert_eq!("(+= a (*= b 2))", parse("a += b *= 2"));
+        assert_eq!("(-= (. a b) (+ 1 2))", parse("a.b -= 1 + 2"));
+        assert_eq!("(%= ([] xs i) 3)", parse("xs[i] %= 3"));
+        assert_eq!("(++ a)", parse("++a"));
+        assert_eq!("(-- ([] (. a xs) 0))", parse("--a.xs[0]"));
+        assert_eq!("(- (a ++))", parse("-a++"));
+        assert_eq!("(** (++ a) 2)", parse("++a ** 2"));
+        assert_eq!("(+ (a --) b)", parse("a-- + b"));
+
+        for source in ["1 += 2;", "++f();", "xs[1:2]--;", "a++++;"] {
+            let tokens = Scanner::new(source.to_string()).scan_tokens().unwrap();
+            let mut ast = Ast::new();
+            let errors = Parser::new(tokens, &mut ast).parse().unwrap_err();
+            assert_eq!(1, errors.len(), "{source}");
+            assert_eq!("Invalid assignment target.", errors[0].message);
+        }
+    }
+
+    #[test]
+    fn synchronizes_after_errors() {
+        let tokens = Scanner::new("var = 1; print (1; var a = 2; {".to_string())
+            .scan_tokens()
+            .unwrap();
+        let mut ast = Ast::new();
+        let messages: Vec<String> = Parser::new(tokens, &mut ast)
+            .parse()
+            .unwrap_err()
+            .into_iter()
+            .map(|e| e.message)
+            .collect();
+        assert_eq!(
+            vec![
+                "Expect variable name.",
+                "Expect ')' after expression.",
+                "Expect '}' after block."
+            ],
+            messages
+        );
+    }
+
+    #[test]
+    fn logical() {
+        assert_eq!("(or a (and b c))", parse("a or b and c"));
+        assert_eq!("(and (== a 1) (!= b 2))", parse("a == 1 and b != 2"));
+        assert_eq!("(?: (or a b) 1 2)", parse("a or b ? 1 : 2"));
+        assert_eq!("(or (or a b) c)", parse("a or b or c"));
+    }
+
+    #[test]
+    fn loop_control_outside_loop() {
+        let messages = |source: &str| {
+            let tokens = Scanner::new(source.to_string()).scan_tokens().unwrap();
+            let mut ast = Ast::new();
+            match Parser::new(tokens, &mut ast).parse() {
+                Ok(_) => vec![],
+                Err(errors) => errors.into_iter().map(|e| e.message).collect::<Vec<_>>(),
+            }
+        };
+
+        assert_eq!(
+            vec!["Can't use 'break' outside of a loop."],
+            messages("break;")
+        );
+        assert_eq!(
+            vec!["Can't use 'continue' outside of a loop."],
+            messages("if (true) { continue; }")
+        );
+        assert!(messages("while (true) { if (false) break; else continue; }").is_empty());
+        assert!(messages("for (;;) { { break; } }").is_empty());
+        assert!(messages("for (x in xs) { if (x) continue; break; }").is_empty());
+        assert!(messages("for (var x in xs) break;").is_empty());
+    }
+
+    #[test]
+    fn calls() {
+        assert_eq!("(call f)", parse("f()"));
+        assert_eq!("(call (call f 1) 2 (+ 3 4))", parse("f(1)(2, 3 + 4)"));
+        assert_eq!("(- (call f 1 2))", parse("-f(1, 2)"));
+        assert_eq!("(call f (group (, 1 2)))", parse("f((1, 2))"));
+    }
+
+    #[test]
+    fn properties() {
+        assert_eq!("(call (. (. a b) c) 1)", parse("a.b.c(1)"));
+        assert_eq!("(= (. (call a) b) (= (. c d) 1))", parse("a().b = c.d = 1"));
+        assert_eq!("(. this x)", parse("this.x"));
+        assert_eq!("(call (super m) 1)", parse("super.m(1)"));
+    }
+
+    #[test]
+    fn arithmetic_operators() {
+        assert_eq!("(+ 1 (% 2 3))", parse("1 + 2 % 3"));
+        assert_eq!("(* (~/ 7 2) 3)", parse("7 ~/ 2 * 3"));
+        assert_eq!("(** 2 (** 3 2))", parse("2 ** 3 ** 2"));
+        assert_eq!("(- (** 2 2))", parse("-2 ** 2"));
+        assert_eq!("(** 2 (- 1))", parse("2 ** -1"));
+        assert_eq!("(* (** 2 3) 4)", parse("2 ** 3 * 4"));
+        assert_eq!("(** (call f) (. a b))", parse("f() ** a.b"));
+    }
+
+    #[test]
+    fn ranges() {
+        assert_eq!("(.. 1 (+ n 1))", parse("1..n + 1"));
+        assert_eq!("(< (..= 1 5) x)", parse("1..=5 < x"));
+        assert_eq!("(in x (.. 1 5))", parse("x in 1..5"));
+        assert_eq!("(. (group (.. 1 5)) step)", parse("(1..5).step"));
+    }
+
+    #[test]
+    fn lists() {
+        assert_eq!("(list)", parse("[]"));
+        assert_eq!("(list 1 (+ 2 3) (list 4))", parse("[1, 2 + 3, [4],]"));
+        assert_eq!("([] ([] xs 0) (- 1))", parse("xs[0][-1]"));
+        assert_eq!("([:] xs 1 3)", parse("xs[1:3]"));
+        assert_eq!("([:] xs _ (- 1))", parse("xs[:-1]"));
+        assert_eq!("([:] xs _ _)", parse("xs[:]"));
+        assert_eq!("(= ([] (. a xs) i) (list))", parse("a.xs[i] = []"));
+
+        let tokens = Scanner::new("xs[1:2] = 3;".to_string())
+            .scan_tokens()
+            .unwrap();
+        let mut ast = Ast::new();
+        let errors = Parser::new(tokens, &mut ast).parse().unwrap_err();
+        assert_eq!("Invalid assignment target.", errors[0].message);
+    }
+
+    #[test]
+    fn maps() {
+        assert_eq!("(map)", parse("{}"));
+        assert_eq!(
+            "(map a 1 (+ 1 2) (map b c))",
+            parse("{a: 1, 1 + 2: {b: c},}")
+        );
+        assert_eq!("(map (?: a b c) d)", parse("{a ? b : c: d}"));
+
+        // At the start of a statement, braces are a block
+        let tokens = Scanner::new("{} var m = {}; print {\"a\": 1};".to_string())
+            .scan_tokens()
+            .unwrap();
+        let mut ast = Ast::new();
+        let statements = Parser::new(tokens, &mut ast).parse().unwrap();
+        assert!(matches!(ast.stmt(statements[0]), Statement::Block(_)));
+        assert_eq!(3, statements.len());
+    }
+
+    #[test]
+    fn getters_are_methods_only() {
+        let parse_program = |source: &str| {
+            let tokens = Scanner::new(source.to_string()).scan_tokens().unwrap();
+            let mut ast = Ast::new();
+            Parser::new(tokens, &mut ast)
+                .parse()
+                .map(|_| ())
+                .map_err(|errors| errors.into_iter().map(|e| e.message).collect::<Vec<_>>())
+        };
+
+        assert!(parse_program("class A { area { return 1; } class unit { return 2; } }").is_ok());
+        assert_eq!(
+            Err(vec!["Expect '(' after function name.".to_string()]),
+            parse_program("fun area {}")
+        );
+    }
+
+    #[test]
+    fn arity_limits() {
+        let params: Vec<String> = (0..256).map(|i| format!("p{i}")).collect();
+        let source = format!(
+            "fun f({}) {{}} f({});",
+            params.join(", "),
+            params.join(", ")
+        );
+        let tokens = Scanner::new(source).scan_tokens().unwrap();
+        let mut ast = Ast::new();
+        let messages: Vec<String> = Parser::new(tokens, &mut ast)
+            .parse()
+            .unwrap_err()
+            .into_iter()
+            .map(|e| e.message)
+            .collect();
+        assert_eq!(
+            vec![
+                "Can't have more than 255 parameters.",
+                "Can't have more than 255 arguments."
+            ],
+            messages
+        );
+    }
+}
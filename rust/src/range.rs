@@ -0,0 +1,294 @@
+use crate::callable::NativeFunction;
+use crate::error::RuntimeError;
+use crate::list;
+use crate::number::Number;
+use crate::scanner::Token;
+use crate::value::Value;
+use num_bigint::BigInt;
+use num_traits::FromPrimitive;
+use std::{fmt::Display, rc::Rc};
+
+/// The numbers from `start` towards `end` in increments of `step`, made by `start..end` or
+/// `start..=end`. The numbers are worked out as they're needed rather than stored. A range whose
+/// bounds and step are all integers counts exactly in integers, however big they are.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Range {
+    start: Number,
+    end: Number,
+    step: Number,
+    /// Whether `end` itself is included, as in `start..=end`
+    inclusive: bool,
+}
+
+impl Range {
+    /// Creates a range counting up by one
+    pub fn new(start: &Value, end: &Value, inclusive: bool) -> Result<Self, String> {
+        let (Value::Number(start), Value::Number(end)) = (start, end) else {
+            return Err("Range bounds must be numbers.".to_string());
+        };
+
+        Ok(Self {
+            start: start.clone(),
+            end: end.clone(),
+            step: Number::Int(1),
+            inclusive,
+        })
+    }
+
+    /// The same range counting in increments of `step`, which may be negative to count down
+    pub fn with_step(self, step: &Value) -> Result<Self, String> {
+        match step {
+            Value::Number(step) if step.is_zero() => Err("Range step can't be zero.".to_string()),
+            Value::Number(step) => Ok(Self {
+                step: step.clone(),
+                ..self
+            }),
+            other => Err(format!(
+                "Range step must be a number, not a {}.",
+                other.type_name()
+            )),
+        }
+    }
+
+    /// Whether the start, end and step are all integers, so that the range counts exactly
+    fn is_integral(&self) -> bool {
+        [&self.start, &self.end, &self.step]
+            .into_iter()
+            .all(|n| !matches!(n, Number::Float(_)))
+    }
+
+    /// How many numbers are in the range
+    pub fn len(&self) -> Number {
+        let zero = Number::Int(0);
+        let len = if self.is_integral() {
+            // Counting down is counting up over the negated span
+            let (span, step) = if self.step < zero {
+                (self.start.subtract(&self.end), self.step.negate())
+            } else {
+                (self.end.subtract(&self.start), self.step.clone())
+            };
+            let steps = |span: &Number| span.floor_divide(&step).expect("steps are never zero");
+            if self.inclusive {
+                steps(&span).add(&Number::Int(1))
+            } else {
+                // Rounds the number of steps up
+                steps(&span.negate()).negate()
+            }
+        } else {
+            let steps = (self.end.to_f64() - self.start.to_f64()) / self.step.to_f64();
+            let len = if self.inclusive {
+                steps.floor() + 1.0
+            } else {
+                steps.ceil()
+            };
+            // A range running off to infinity is as long as it can be counted
+            Number::big(BigInt::from_f64(len).unwrap_or_else(|| match len > 0.0 {
+                true => BigInt::from(i128::MAX),
+                false => BigInt::ZERO,
+            }))
+        };
+        // Saturates at zero for ranges that count away from their end
+        if len < zero { zero } else { len }
+    }
+
+    /// The number at `position`, which must be less than `len()`
+    pub fn nth(&self, position: &Number) -> Number {
+        if self.is_integral() {
+            self.start.add(&position.multiply(&self.step))
+        } else {
+            Number::Float(self.start.to_f64() + position.to_f64() * self.step.to_f64())
+        }
+    }
+
+    /// The number at the Lox index `index`, which may be negative to count back from the end
+    pub fn get(&self, index: &Value) -> Result<Value, String> {
+        let position = list::position(&self.len(), index, "range")?;
+        Ok(Value::Number(self.nth(&position)))
+    }
+
+    /// Whether `value` is one of the numbers in the range
+    pub fn contains(&self, value: &Value) -> bool {
+        let Value::Number(n) = value else {
+            return false;
+        };
+
+        let steps = if self.is_integral() {
+            // A fraction is never in a range of integers
+            let Some(n) = n.to_bigint() else {
+                return false;
+            };
+            let offset = Number::big(n).subtract(&self.start);
+            match (offset.modulo(&self.step), offset.floor_divide(&self.step)) {
+                (Ok(remainder), Ok(steps)) if remainder.is_zero() => steps,
+                _ => return false,
+            }
+        } else {
+            let steps = (n.to_f64() - self.start.to_f64()) / self.step.to_f64();
+            if steps.fract() != 0.0 {
+                return false;
+            }
+            Number::Float(steps)
+        };
+        steps >= Number::Int(0) && steps < self.len()
+    }
+}
+
+impl Display for Range {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let operator = if self.inclusive { "..=" } else { ".." };
+        let (start, end) = (&self.start, &self.end);
+        if matches!(self.step, Number::Int(1)) {
+            write!(f, "{start}{operator}{end}")
+        } else {
+            write!(f, "({start}{operator}{end}).step({})", self.step)
+        }
+    }
+}
+
+/// Looks up the built-in method `name` on `range`, bound to it
+pub fn method(range: Range, name: &Token) -> Result<Value, RuntimeError> {
+    let method = match name.lexeme.as_str() {
+        "step" => NativeFunction::new("step", 1, move |_, args| {
+            Ok(Value::Range(range.clone().with_step(&args[0])?))
+        }),
+        "contains" => NativeFunction::new("contains", 1, move |_, args| {
+            Ok(Value::Boolean(range.contains(&args[0])))
+        }),
+        "len" => NativeFunction::new("len", 0, move |_, _| Ok(Value::Number(range.len()))),
+        _ => {
+            return Err(RuntimeError::new(
+                name,
+                format!("Undefined property '{}'.", name.lexeme),
+            ));
+        }
+    };
+
+    Ok(Value::Callable(Rc::new(method)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::tests::run;
+
+    fn range(
+        start: impl Into<Number>,
+        end: i64,
+        step: impl Into<Number>,
+        inclusive: bool,
+    ) -> Range {
+        let (start, end) = (Value::Number(start.into()), Value::Number(end.into()));
+        Range::new(&start, &end, inclusive)
+            .unwrap()
+            .with_step(&Value::Number(step.into()))
+            .unwrap()
+    }
+
+    #[test]
+    fn len() {
+        assert_eq!(Number::Int(4), range(1, 5, 1, false).len());
+        assert_eq!(Number::Int(5), range(1, 5, 1, true).len());
+        assert_eq!(Number::Int(3), range(0, 5, 2, false).len());
+        assert_eq!(Number::Int(3), range(0, 4, 2, true).len());
+        assert_eq!(Number::Int(0), range(5, 1, 1, false).len());
+        assert_eq!(Number::Int(4), range(5, 1, -1, false).len());
+        assert_eq!(Number::Int(0), range(1, 1, 1, false).len());
+        assert_eq!(Number::Int(1), range(1, 1, 1, true).len());
+        assert_eq!(Number::Int(4), range(0.5, 2, 0.5, true).len());
+        assert_eq!(Number::Int(3), range(0, 1, 0.4, false).len());
+    }
+
+    #[test]
+    fn contains() {
+        let evens = range(0, 10, 2, false);
+        assert!(evens.contains(&Value::Number(Number::Int(4))));
+        assert!(!evens.contains(&Value::Number(Number::Int(5))));
+        assert!(!evens.contains(&Value::Number(Number::Int(10))));
+        assert!(!evens.contains(&Value::Number(Number::Int(-2))));
+        assert!(range(0, 10, 2, true).contains(&Value::Number(Number::Int(10))));
+        assert!(!evens.contains(&Value::String("4".to_string())));
+        assert!(evens.contains(&Value::Number(Number::Float(4.0))));
+        assert!(!evens.contains(&Value::Number(Number::Float(4.5))));
+        assert!(range(0, 2, 0.5, false).contains(&Value::Number(Number::Float(1.5))));
+    }
+
+    #[test]
+    fn in_lox() {
+        let source = "
+            var r = 1..5;
+            print r;
+            print 1..=5;
+            print (0..10).step(3);
+            print r[0];
+            print r[-1];
+            print r.len();
+            print 3 in r;
+            print 5 in r;
+            print (10..0).step(-5).contains(5);
+            for (i in (10..=0).step(-5)) print i;
+            var n = 3;
+            for (i in 0..n + 1) print i;
+        ";
+        let (output, err) = run(source);
+        assert_eq!(
+            "1..5\n1..=5\n(0..10).step(3)\n1\n4\n4\ntrue\nfalse\ntrue\n10\n5\n0\n0\n1\n2\n3\n",
+            output
+        );
+        assert!(err.is_none());
+    }
+
+    #[test]
+    fn longer_than_an_integer() {
+        let source = "
+            var r = -9223372036854775807..9223372036854775807;
+            print r.len();
+            print r[-1];
+            print r[9223372036854775808];
+            print 9223372036854775806 in r;
+            print (-9223372036854775808..=9223372036854775807).len();
+        ";
+        let (output, err) = run(source);
+        assert_eq!(
+            "18446744073709551614\n9223372036854775806\n1\ntrue\n18446744073709551616\n",
+            output
+        );
+        assert!(err.is_none());
+    }
+
+    #[test]
+    fn big_bounds() {
+        let source = "
+            for (i in 100000000000000000000..=100000000000000000002) print i;
+            var r = 100000000000000000000..100000000000000000005;
+            print len(r);
+            print r[-1];
+            print 100000000000000000003 in r;
+            print 100000000000000000005 in r;
+            print (0..10 ** 40).len();
+            print (0..10 ** 40)[-1];
+        ";
+        let (output, err) = run(source);
+        assert_eq!(
+            "100000000000000000000\n100000000000000000001\n100000000000000000002\n5\n\
+             100000000000000000004\ntrue\nfalse\n10000000000000000000000000000000000000000\n\
+             9999999999999999999999999999999999999999\n",
+            output
+        );
+        assert!(err.is_none());
+    }
+
+    #[test]
+    fn errors() {
+        let (_, err) = run("print 1..\"a\";");
+        assert_eq!("Range bounds must be numbers.", err.unwrap().message);
+
+        let (_, err) = run("print (1..2).step(0);");
+        assert_eq!("Range step can't be zero.", err.unwrap().message);
+
+        let (_, err) = run("print (1..3)[2];");
+        assert_eq!(
+            "Index 2 is out of range for a range of length 2.",
+            err.unwrap().message
+        );
+    }
+}
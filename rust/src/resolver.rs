@@ -0,0 +1,423 @@
+use crate::ast::{Ast, ExprId, StmtId};
+use crate::expression::Expression;
+use crate::interpreter::Interpreter;
+use crate::scanner::Token;
+use crate::statement::Statement;
+use std::collections::HashMap;
+
+#[derive(Debug)]
+pub struct ResolveError {
+    pub token: Token,
+    pub message: String,
+}
+
+/// The kind of function whose body is being resolved
+#[derive(Clone, Copy, PartialEq)]
+enum FunctionType {
+    None,
+    Function,
+    Method,
+    Initializer,
+}
+
+/// The kind of class whose body is being resolved
+#[derive(Clone, Copy, PartialEq)]
+enum ClassType {
+    None,
+    Class,
+    Subclass,
+    Trait,
+}
+
+/// Static pass that works out which scope every variable reference refers to, telling the
+/// interpreter how many environments to walk up for it. References that aren't found in any
+/// local scope are left for the interpreter to look up in the globals.
+pub struct Resolver<'a> {
+    ast: &'a Ast,
+    interpreter: &'a mut Interpreter,
+    /// Local scopes, innermost last, mapping each name to whether its initializer has finished
+    scopes: Vec<HashMap<String, bool>>,
+    current_function: FunctionType,
+    current_class: ClassType,
+    errors: Vec<ResolveError>,
+}
+
+impl<'a> Resolver<'a> {
+    pub fn new(ast: &'a Ast, interpreter: &'a mut Interpreter) -> Self {
+        Self {
+            ast,
+            interpreter,
+            scopes: vec![],
+            current_function: FunctionType::None,
+            current_class: ClassType::None,
+            errors: vec![],
+        }
+    }
+
+    /// Resolves a program, returning every error found
+    pub fn resolve(&mut self, statements: &[StmtId]) -> Result<(), Vec<ResolveError>> {
+        self.resolve_statements(statements);
+        self.finish()
+    }
+
+    /// Resolves a single top-level expression, returning every error found
+    pub fn resolve_expression(&mut self, expr: ExprId) -> Result<(), Vec<ResolveError>> {
+        self.resolve_expr(expr);
+        self.finish()
+    }
+
+    fn finish(&mut self) -> Result<(), Vec<ResolveError>> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(std::mem::take(&mut self.errors))
+        }
+    }
+
+    fn resolve_statements(&mut self, statements: &[StmtId]) {
+        for stmt in statements {
+            self.resolve_stmt(*stmt);
+        }
+    }
+
+    fn resolve_stmt(&mut self, id: StmtId) {
+        match self.ast.stmt(id) {
+            Statement::Expression(expr) => self.resolve_expr(*expr),
+            Statement::Print(stmt) => self.resolve_expr(stmt.value),
+            Statement::Var(var) => {
+                // Declared before the initializer is resolved so that reading the variable
+                // there can be caught
+                self.declare(&var.name);
+                if let Some(initializer) = var.initializer {
+                    self.resolve_expr(initializer);
+                }
+                self.define(&var.name);
+            }
+            Statement::Block(statements) => {
+                self.begin_scope();
+                self.resolve_statements(statements);
+                self.end_scope();
+            }
+            Statement::If(stmt) => {
+                self.resolve_expr(stmt.condition);
+                self.resolve_stmt(stmt.then_branch);
+                if let Some(else_branch) = stmt.else_branch {
+                    self.resolve_stmt(else_branch);
+                }
+            }
+            Statement::While(stmt) => {
+                self.resolve_expr(stmt.condition);
+                self.resolve_stmt(stmt.body);
+                if let Some(increment) = stmt.increment {
+                    self.resolve_expr(increment);
+                }
+            }
+            Statement::ForIn(stmt) => {
+                self.resolve_expr(stmt.iterable);
+                // The loop variable gets a scope of its own around the body
+                self.begin_scope();
+                self.declare(&stmt.name);
+                self.define(&stmt.name);
+                self.resolve_stmt(stmt.body);
+                self.end_scope();
+            }
+            Statement::Break | Statement::Continue => (),
+            Statement::Function(function) => {
+                // Defined straight away so that the function can refer to itself
+                self.declare(&function.name);
+                self.define(&function.name);
+                self.resolve_function(id, FunctionType::Function);
+            }
+            Statement::Return(stmt) => {
+                if self.current_function == FunctionType::None {
+                    self.error(&stmt.keyword, "Can't return from top-level code.");
+                }
+                if let Some(value) = stmt.value {
+                    if self.current_function == FunctionType::Initializer {
+                        self.error(&stmt.keyword, "Can't return a value from an initializer.");
+                    }
+                    self.resolve_expr(value);
+                }
+            }
+            Statement::Class(class) => {
+                let enclosing = std::mem::replace(&mut self.current_class, ClassType::Class);
+                self.declare(&class.name);
+                self.define(&class.name);
+
+                if let Some(superclass) = class.superclass {
+                    self.current_class = ClassType::Subclass;
+                    if let Expression::Variable(variable) = self.ast.expr(superclass)
+                        && variable.name.lexeme == class.name.lexeme
+                    {
+                        self.error(&variable.name, "A class can't inherit from itself.");
+                    }
+                    self.resolve_expr(superclass);
+                }
+                for name in class.traits.iter() {
+                    self.resolve_expr(*name);
+                }
+
+                if class.superclass.is_some() {
+                    // Methods of a subclass close over a scope that binds `super`
+                    self.begin_scope();
+                    self.define_name("super");
+                }
+
+                // Methods close over a scope that binds `this`
+                self.begin_scope();
+                self.define_name("this");
+                self.resolve_methods(&class.methods);
+                // `this` in a class method is the class itself, and `init` is nothing special
+                for method in class.class_methods.iter() {
+                    self.resolve_function(*method, FunctionType::Method);
+                }
+                self.end_scope();
+
+                if class.superclass.is_some() {
+                    self.end_scope();
+                }
+                self.current_class = enclosing;
+            }
+            Statement::Trait(stmt) => {
+                let enclosing = std::mem::replace(&mut self.current_class, ClassType::Trait);
+                self.declare(&stmt.name);
+                self.define(&stmt.name);
+
+                self.begin_scope();
+                self.define_name("this");
+                self.resolve_methods(&stmt.methods);
+                self.end_scope();
+
+                self.current_class = enclosing;
+            }
+        }
+    }
+
+    /// Resolves the instance methods of a class or trait, treating `init` as an initializer
+    fn resolve_methods(&mut self, methods: &[StmtId]) {
+        for method in methods {
+            let Statement::Function(function) = self.ast.stmt(*method) else {
+                unreachable!("the parser only puts functions in class bodies");
+            };
+            let function_type = if function.name.lexeme == "init" {
+                FunctionType::Initializer
+            } else {
+                FunctionType::Method
+            };
+            self.resolve_function(*method, function_type);
+        }
+    }
+
+    /// Resolves the parameters and body of the function declared by `id`
+    fn resolve_function(&mut self, id: StmtId, function_type: FunctionType) {
+        let Statement::Function(function) = self.ast.stmt(id) else {
+            unreachable!("only function declarations have bodies to resolve");
+        };
+
+        let enclosing = std::mem::replace(&mut self.current_function, function_type);
+        self.begin_scope();
+        for param in function.params.iter() {
+            self.declare(param);
+            self.define(param);
+        }
+        self.resolve_statements(&function.body);
+        self.end_scope();
+        self.current_function = enclosing;
+    }
+
+    fn resolve_expr(&mut self, id: ExprId) {
+        match self.ast.expr(id) {
+            Expression::Variable(variable) => {
+                let in_initializer = self
+                    .scopes
+                    .last()
+                    .is_some_and(|scope| scope.get(&variable.name.lexeme) == Some(&false));
+                if in_initializer {
+                    self.error(
+                        &variable.name,
+                        "Can't read local variable in its own initializer.",
+                    );
+                }
+                self.resolve_local(id, &variable.name);
+            }
+            Expression::Assign(assign) => {
+                self.resolve_expr(assign.value);
+                self.resolve_local(id, &assign.name);
+            }
+            Expression::This(this) => {
+                if self.current_class == ClassType::None {
+                    self.error(&this.keyword, "Can't use 'this' outside of a class.");
+                    return;
+                }
+                self.resolve_local(id, &this.keyword);
+            }
+            Expression::Super(expr) => {
+                match self.current_class {
+                    ClassType::None => {
+                        self.error(&expr.keyword, "Can't use 'super' outside of a class.");
+                    }
+                    ClassType::Class => self.error(
+                        &expr.keyword,
+                        "Can't use 'super' in a class with no superclass.",
+                    ),
+                    ClassType::Trait => self.error(&expr.keyword, "Can't use 'super' in a trait."),
+                    ClassType::Subclass => (),
+                }
+                self.resolve_local(id, &expr.keyword);
+            }
+            expr => {
+                for child in expr.children() {
+                    self.resolve_expr(child);
+                }
+            }
+        }
+    }
+
+    /// Records how many scopes out `name` was declared, if it's a local
+    fn resolve_local(&mut self, id: ExprId, name: &Token) {
+        let found = self
+            .scopes
+            .iter()
+            .rev()
+            .position(|scope| scope.contains_key(&name.lexeme));
+        if let Some(depth) = found {
+            self.interpreter.resolve(id, depth);
+        }
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn end_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    /// Adds `name` to the innermost scope, marked as not ready to be read yet
+    fn declare(&mut self, name: &Token) {
+        let Some(scope) = self.scopes.last_mut() else {
+            return;
+        };
+
+        if scope.insert(name.lexeme.to_owned(), false).is_some() {
+            self.error(name, "Already a variable with this name in this scope.");
+        }
+    }
+
+    /// Marks `name` as ready to be read
+    fn define(&mut self, name: &Token) {
+        self.define_name(&name.lexeme);
+    }
+
+    /// Like `define`, for names such as `this` that don't come from a token
+    fn define_name(&mut self, name: &str) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string(), true);
+        }
+    }
+
+    fn error(&mut self, token: &Token, msg: &str) {
+        self.errors.push(ResolveError {
+            token: token.clone(),
+            message: msg.to_string(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+
+    fn errors(source: &str) -> Vec<String> {
+        let tokens = Scanner::new(source.to_string()).scan_tokens().unwrap();
+        let mut ast = Ast::new();
+        let statements = Parser::new(tokens, &mut ast).parse().unwrap();
+        let mut interpreter = Interpreter::new();
+        match Resolver::new(&ast, &mut interpreter).resolve(&statements) {
+            Ok(()) => vec![],
+            Err(errors) => errors.into_iter().map(|e| e.message).collect(),
+        }
+    }
+
+    #[test]
+    fn own_initializer() {
+        assert_eq!(
+            vec!["Can't read local variable in its own initializer."],
+            errors("var a = 1; { var a = a; }")
+        );
+        // Globals are looked up dynamically, so this is allowed
+        assert!(errors("var a = 1; var a = a;").is_empty());
+    }
+
+    #[test]
+    fn redeclaration() {
+        assert_eq!(
+            vec!["Already a variable with this name in this scope."],
+            errors("{ var a = 1; var a = 2; }")
+        );
+        assert_eq!(
+            vec!["Already a variable with this name in this scope."],
+            errors("fun f(a, a) {}")
+        );
+        assert!(errors("{ var a = 1; { var a = 2; } }").is_empty());
+    }
+
+    #[test]
+    fn top_level_return() {
+        assert_eq!(
+            vec!["Can't return from top-level code."],
+            errors("return 1;")
+        );
+        assert!(errors("fun f() { return 1; }").is_empty());
+    }
+
+    #[test]
+    fn this_outside_class() {
+        assert_eq!(
+            vec!["Can't use 'this' outside of a class."],
+            errors("print this;")
+        );
+        assert_eq!(
+            vec!["Can't use 'this' outside of a class."],
+            errors("fun f() { return this; }")
+        );
+        assert!(errors("class A { m() { fun f() { return this; } } }").is_empty());
+    }
+
+    #[test]
+    fn initializer_return() {
+        assert_eq!(
+            vec!["Can't return a value from an initializer."],
+            errors("class A { init() { return 1; } }")
+        );
+        assert!(errors("class A { init() { return; } }").is_empty());
+    }
+
+    #[test]
+    fn super_errors() {
+        assert_eq!(
+            vec!["A class can't inherit from itself."],
+            errors("class A < A {}")
+        );
+        assert_eq!(
+            vec!["Can't use 'super' outside of a class."],
+            errors("super.m();")
+        );
+        assert_eq!(
+            vec!["Can't use 'super' in a class with no superclass."],
+            errors("class A { m() { super.m(); } }")
+        );
+        assert!(errors("class A {} class B < A { m() { super.m(); } }").is_empty());
+    }
+
+    #[test]
+    fn traits() {
+        assert!(errors("trait T { m() { return this; } } class A with T {}").is_empty());
+        assert_eq!(
+            vec!["Can't use 'super' in a trait."],
+            errors("trait T { m() { return super.m(); } }")
+        );
+    }
+}
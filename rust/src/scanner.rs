@@ -1,4 +1,6 @@
-use crate::error::lox_error;
+use crate::error::{LoxError, lox_error};
+use crate::number::Number;
+use crate::span::Span;
 use std::fmt::Display;
 
 #[derive(Clone, PartialEq, Debug)]
@@ -7,13 +9,29 @@ pub enum TokenType {
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
     Comma,
     Dot,
+    DotDot,
+    DotDotEqual,
     Minus,
+    MinusMinus,
+    MinusEqual,
     Plus,
+    PlusPlus,
+    PlusEqual,
     Semicolon,
     Slash,
+    SlashEqual,
     Star,
+    StarStar,
+    StarEqual,
+    Percent,
+    PercentEqual,
+    TildeSlash,
+    Question,
+    Colon,
     Bang,
     BangEqual,
     Equal,
@@ -26,21 +44,27 @@ pub enum TokenType {
     String,
     Number,
     And,
+    Break,
     Class,
+    Continue,
     Else,
     False,
     Fun,
     For,
     If,
+    In,
     Nil,
     Or,
     Print,
     Return,
     Super,
     This,
+    Trait,
     True,
     Var,
     While,
+    With,
+    #[allow(clippy::upper_case_acronyms)]
     EOF,
 }
 
@@ -51,13 +75,29 @@ impl Display for TokenType {
             TokenType::RightParen => write!(f, "RightParen"),
             TokenType::LeftBrace => write!(f, "LeftBrace"),
             TokenType::RightBrace => write!(f, "RightBrace"),
+            TokenType::LeftBracket => write!(f, "LeftBracket"),
+            TokenType::RightBracket => write!(f, "RightBracket"),
             TokenType::Comma => write!(f, "Comma"),
             TokenType::Dot => write!(f, "Dot"),
+            TokenType::DotDot => write!(f, "DotDot"),
+            TokenType::DotDotEqual => write!(f, "DotDotEqual"),
             TokenType::Minus => write!(f, "Minus"),
+            TokenType::MinusMinus => write!(f, "MinusMinus"),
+            TokenType::MinusEqual => write!(f, "MinusEqual"),
             TokenType::Plus => write!(f, "Plus"),
+            TokenType::PlusPlus => write!(f, "PlusPlus"),
+            TokenType::PlusEqual => write!(f, "PlusEqual"),
             TokenType::Semicolon => write!(f, "Semicolon"),
             TokenType::Slash => write!(f, "Slash"),
+            TokenType::SlashEqual => write!(f, "SlashEqual"),
             TokenType::Star => write!(f, "Star"),
+            TokenType::StarStar => write!(f, "StarStar"),
+            TokenType::StarEqual => write!(f, "StarEqual"),
+            TokenType::Percent => write!(f, "Percent"),
+            TokenType::PercentEqual => write!(f, "PercentEqual"),
+            TokenType::TildeSlash => write!(f, "TildeSlash"),
+            TokenType::Question => write!(f, "Question"),
+            TokenType::Colon => write!(f, "Colon"),
             TokenType::Bang => write!(f, "Bang"),
             TokenType::BangEqual => write!(f, "BangEqual"),
             TokenType::Equal => write!(f, "Equal"),
@@ -70,21 +110,26 @@ impl Display for TokenType {
             TokenType::String => write!(f, "String"),
             TokenType::Number => write!(f, "Number"),
             TokenType::And => write!(f, "And"),
+            TokenType::Break => write!(f, "Break"),
             TokenType::Class => write!(f, "Class"),
+            TokenType::Continue => write!(f, "Continue"),
             TokenType::Else => write!(f, "Else"),
             TokenType::False => write!(f, "False"),
             TokenType::Fun => write!(f, "Fun"),
             TokenType::For => write!(f, "For"),
             TokenType::If => write!(f, "If"),
+            TokenType::In => write!(f, "In"),
             TokenType::Nil => write!(f, "Nil"),
             TokenType::Or => write!(f, "Or"),
             TokenType::Print => write!(f, "Print"),
             TokenType::Return => write!(f, "Return"),
             TokenType::Super => write!(f, "Super"),
             TokenType::This => write!(f, "This"),
+            TokenType::Trait => write!(f, "Trait"),
             TokenType::True => write!(f, "True"),
             TokenType::Var => write!(f, "Var"),
             TokenType::While => write!(f, "While"),
+            TokenType::With => write!(f, "With"),
             TokenType::EOF => write!(f, "EOF"),
         }
     }
@@ -93,7 +138,7 @@ impl Display for TokenType {
 #[derive(Clone, PartialEq, Debug)]
 pub enum TokenLiteral {
     Str(String),
-    Num(f64),
+    Num(Number),
     None,
 }
 
@@ -107,22 +152,13 @@ impl Display for TokenLiteral {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Token {
     pub token_type: TokenType,
     pub lexeme: String,
     pub literal: TokenLiteral,
-    #[allow(dead_code)] // TODO
     pub line: usize,
-}
-
-impl PartialEq for Token {
-    fn eq(&self, other: &Self) -> bool {
-        self.lexeme == other.lexeme
-            && self.line == other.line
-            && self.literal == other.literal
-            && self.token_type == other.token_type
-    }
+    pub span: Span,
 }
 
 impl Display for Token {
@@ -136,12 +172,13 @@ impl Display for Token {
 }
 
 impl Token {
-    pub fn new(token_type: TokenType, lexeme: String, literal: TokenLiteral, line: usize) -> Self {
+    pub fn new(token_type: TokenType, lexeme: String, literal: TokenLiteral, span: Span) -> Self {
         Self {
             token_type,
             lexeme,
-            line,
+            line: span.line,
             literal,
+            span,
         }
     }
 }
@@ -152,6 +189,7 @@ pub struct Scanner {
     current: usize,
     line: usize,
     tokens: Vec<Token>,
+    had_error: bool,
 }
 
 impl Scanner {
@@ -162,11 +200,13 @@ impl Scanner {
             current: 0,
             line: 1,
             tokens: vec![],
+            had_error: false,
         }
     }
 
-    /// Scans the tokens of a source string
-    pub fn scan_tokens(&mut self) -> Vec<Token> {
+    /// Scans the tokens of a source string. Errors are reported as they're found, and the whole
+    /// source is still scanned so that they're all reported at once.
+    pub fn scan_tokens(&mut self) -> Result<Vec<Token>, LoxError> {
         while !self.is_at_end() {
             self.start = self.current;
             self.scan_token();
@@ -176,14 +216,24 @@ impl Scanner {
             TokenType::EOF,
             String::new(),
             TokenLiteral::None,
-            self.line,
+            Span::new(self.current, self.current, self.line),
         ));
-        self.tokens.clone()
+
+        if self.had_error {
+            return Err(LoxError::Static);
+        }
+        Ok(self.tokens.clone())
+    }
+
+    /// Reports an error at the current line
+    fn error(&mut self, msg: String) {
+        lox_error(self.line, msg);
+        self.had_error = true;
     }
 
     /// Indicates whether the scanner is at the end of a line
     fn is_at_end(&self) -> bool {
-        return self.current >= self.source.len();
+        self.current >= self.source.len()
     }
 
     /// Scans a token
@@ -195,12 +245,62 @@ impl Scanner {
             ')' => self.add_token(TokenType::RightParen, None),
             '{' => self.add_token(TokenType::LeftBrace, None),
             '}' => self.add_token(TokenType::RightBrace, None),
+            '[' => self.add_token(TokenType::LeftBracket, None),
+            ']' => self.add_token(TokenType::RightBracket, None),
             ',' => self.add_token(TokenType::Comma, None),
-            '.' => self.add_token(TokenType::Dot, None),
-            '-' => self.add_token(TokenType::Minus, None),
-            '+' => self.add_token(TokenType::Plus, None),
+            '.' => {
+                let token = if !self.match_token('.') {
+                    TokenType::Dot
+                } else if self.match_token('=') {
+                    TokenType::DotDotEqual
+                } else {
+                    TokenType::DotDot
+                };
+                self.add_token(token, None);
+            }
+            '-' => {
+                let token = if self.match_token('-') {
+                    TokenType::MinusMinus
+                } else if self.match_token('=') {
+                    TokenType::MinusEqual
+                } else {
+                    TokenType::Minus
+                };
+                self.add_token(token, None);
+            }
+            '+' => {
+                let token = if self.match_token('+') {
+                    TokenType::PlusPlus
+                } else if self.match_token('=') {
+                    TokenType::PlusEqual
+                } else {
+                    TokenType::Plus
+                };
+                self.add_token(token, None);
+            }
             ';' => self.add_token(TokenType::Semicolon, None),
-            '*' => self.add_token(TokenType::Star, None),
+            '*' => {
+                let token = if self.match_token('*') {
+                    TokenType::StarStar
+                } else if self.match_token('=') {
+                    TokenType::StarEqual
+                } else {
+                    TokenType::Star
+                };
+                self.add_token(token, None);
+            }
+            '%' => {
+                let token = if self.match_token('=') {
+                    TokenType::PercentEqual
+                } else {
+                    TokenType::Percent
+                };
+                self.add_token(token, None);
+            }
+            // Integer division, since `//` starts a comment
+            '~' if self.match_token('/') => self.add_token(TokenType::TildeSlash, None),
+            '?' => self.add_token(TokenType::Question, None),
+            ':' => self.add_token(TokenType::Colon, None),
             '!' => {
                 let token = if self.match_token('=') {
                     TokenType::BangEqual
@@ -254,6 +354,8 @@ impl Scanner {
                             self.line += 1;
                         }
                     }
+                } else if self.match_token('=') {
+                    self.add_token(TokenType::SlashEqual, None);
                 } else {
                     self.add_token(TokenType::Slash, None);
                 }
@@ -267,7 +369,7 @@ impl Scanner {
                 } else if self.is_valid_identifier_char(character) {
                     self.identifier();
                 } else {
-                    lox_error(self.line, format!("Unexpected character: {character}"));
+                    self.error(format!("Unexpected character: {character}"));
                 }
             }
         }
@@ -275,20 +377,22 @@ impl Scanner {
 
     /// Returns the current character and increments a pointer to the next character
     fn advance(&mut self) -> char {
-        let ch = self.source.chars().nth(self.current).unwrap();
-        self.current += 1;
+        let ch = self.peek();
+        // Positions are byte offsets, so that spans can slice the source
+        self.current += ch.len_utf8();
         ch
     }
 
     /// Parses a portion of the source string into a token and appends it to the list of tokens
     fn add_token(&mut self, token_type: TokenType, literal: Option<TokenLiteral>) {
         let text = self.source[self.start..self.current].to_string();
-        if let Some(v) = literal {
-            self.tokens.push(Token::new(token_type, text, v, self.line));
-        } else {
-            self.tokens
-                .push(Token::new(token_type, text, TokenLiteral::None, self.line));
-        }
+        let span = self.span();
+        self.tokens.push(Token::new(
+            token_type,
+            text,
+            literal.unwrap_or(TokenLiteral::None),
+            span,
+        ));
     }
 
     /// Indicates whether `expected` matches the current token and increments a pointer to the next character
@@ -297,28 +401,29 @@ impl Scanner {
         if self.is_at_end() {
             return false;
         }
-        if self.source.chars().nth(self.current).unwrap() != expected {
+        if self.peek() != expected {
             return false;
         };
 
-        self.current += 1;
-        return true;
+        self.current += expected.len_utf8();
+        true
+    }
+
+    /// The span of the token currently being scanned
+    fn span(&self) -> Span {
+        Span::new(self.start, self.current, self.line)
     }
 
     /// Looks at the current character without advancing the pointer
     fn peek(&self) -> char {
-        if self.is_at_end() {
-            return '\0';
-        }
-        self.source.chars().nth(self.current).unwrap()
+        self.source[self.current..].chars().next().unwrap_or('\0')
     }
 
     /// Looks at the next character without advancing the pointer
     fn peek_next(&self) -> char {
-        if self.current + 1 >= self.source.len() {
-            return '\0';
-        }
-        self.source.chars().nth(self.current + 1).unwrap()
+        let mut chars = self.source[self.current..].chars();
+        chars.next();
+        chars.next().unwrap_or('\0')
     }
 
     /// Parses a value surrounded by `""`
@@ -331,7 +436,7 @@ impl Scanner {
         }
 
         if self.is_at_end() {
-            lox_error(self.line, "Unterminated string.".to_string());
+            self.error("Unterminated string.".to_string());
             return;
         }
 
@@ -346,7 +451,7 @@ impl Scanner {
             TokenType::String,
             literal.to_owned(),
             TokenLiteral::Str(literal),
-            self.line,
+            self.span(),
         ));
     }
 
@@ -356,6 +461,8 @@ impl Scanner {
             self.advance();
         }
 
+        // Only a `.` followed by a digit starts a fraction, so `1..5` is a range rather than `1.`
+        // followed by `.5`
         if self.peek() == '.' && self.peek_next().is_ascii_digit() {
             self.advance();
         }
@@ -364,12 +471,18 @@ impl Scanner {
             self.advance();
         }
 
-        let number: f64 = match self.source[self.start..self.current].parse() {
-            Ok(n) => n,
-            Err(e) => {
-                lox_error(self.line, format!("Unable to parse number: {}", e));
-                return;
+        let text = &self.source[self.start..self.current];
+        let number = if text.contains('.') {
+            match text.parse() {
+                Ok(n) => Number::Float(n),
+                Err(e) => {
+                    self.error(format!("Unable to parse number: {}", e));
+                    return;
+                }
             }
+        } else {
+            // Numbers without a fraction are integers, as big as they need to be
+            Number::parse_integer(text, 10).expect("digits always make an integer")
         };
 
         self.add_token(TokenType::Number, Some(TokenLiteral::Num(number)));
@@ -389,21 +502,26 @@ impl Scanner {
         let literal = self.source[self.start..self.current].to_string();
         let token_type = match literal.as_str() {
             "and" => TokenType::And,
+            "break" => TokenType::Break,
             "class" => TokenType::Class,
+            "continue" => TokenType::Continue,
             "else" => TokenType::Else,
             "false" => TokenType::False,
             "for" => TokenType::For,
             "fun" => TokenType::Fun,
             "if" => TokenType::If,
+            "in" => TokenType::In,
             "nil" => TokenType::Nil,
             "or" => TokenType::Or,
             "print" => TokenType::Print,
             "return" => TokenType::Return,
             "super" => TokenType::Super,
             "this" => TokenType::This,
+            "trait" => TokenType::Trait,
             "true" => TokenType::True,
             "var" => TokenType::Var,
             "while" => TokenType::While,
+            "with" => TokenType::With,
             _ => TokenType::Identifier,
         };
 
@@ -424,7 +542,7 @@ mod tests {
                 TokenType::$token_type,
                 $lexeme.to_string(),
                 $token_literal,
-                1 as usize,
+                Span::new(0, 0, 1),
             )
         };
         ($token_type:ident, $lexeme:expr) => {
@@ -432,7 +550,7 @@ mod tests {
                 TokenType::$token_type,
                 $lexeme.to_string(),
                 TokenLiteral::None,
-                1usize,
+                Span::new(0, 0, 1),
             )
         };
     }
@@ -440,21 +558,30 @@ mod tests {
     #[test]
     fn basic() {
         let mut scanner = Scanner::new(String::from(
-            "() {} , . - + ; / * ! != = == > >= < <= this_is_an_identifier \"Hello, world!\" 123.456 and class else false fun for if nil or print return super this true var while",
+            "() {} [] , . .. ..= - + ; / * ** % ~/ ? : ! != = == > >= < <= this_is_an_identifier \"Hello, world!\" 123.456 and break class continue else false fun for if in nil or print return super this trait true var while with",
         ));
-        let tokens = scanner.scan_tokens();
+        let tokens = scanner.scan_tokens().unwrap();
         let expected_tokens = vec![
             new_token!(LeftParen, "("),
             new_token!(RightParen, ")"),
             new_token!(LeftBrace, "{"),
             new_token!(RightBrace, "}"),
+            new_token!(LeftBracket, "["),
+            new_token!(RightBracket, "]"),
             new_token!(Comma, ","),
             new_token!(Dot, "."),
+            new_token!(DotDot, ".."),
+            new_token!(DotDotEqual, "..="),
             new_token!(Minus, "-"),
             new_token!(Plus, "+"),
             new_token!(Semicolon, ";"),
             new_token!(Slash, "/"),
             new_token!(Star, "*"),
+            new_token!(StarStar, "**"),
+            new_token!(Percent, "%"),
+            new_token!(TildeSlash, "~/"),
+            new_token!(Question, "?"),
+            new_token!(Colon, ":"),
             new_token!(Bang, "!"),
             new_token!(BangEqual, "!="),
             new_token!(Equal, "="),
@@ -473,29 +600,144 @@ mod tests {
                 "Hello, world!",
                 TokenLiteral::Str("Hello, world!".to_string())
             ),
-            new_token!(Number, "123.456", TokenLiteral::Num(123.456)),
+            new_token!(Number, "123.456", TokenLiteral::Num(Number::Float(123.456))),
             new_token!(And, "and"),
+            new_token!(Break, "break"),
             new_token!(Class, "class"),
+            new_token!(Continue, "continue"),
             new_token!(Else, "else"),
             new_token!(False, "false"),
             new_token!(Fun, "fun"),
             new_token!(For, "for"),
             new_token!(If, "if"),
+            new_token!(In, "in"),
             new_token!(Nil, "nil"),
             new_token!(Or, "or"),
             new_token!(Print, "print"),
             new_token!(Return, "return"),
             new_token!(Super, "super"),
             new_token!(This, "this"),
+            new_token!(Trait, "trait"),
             new_token!(True, "true"),
             new_token!(Var, "var"),
             new_token!(While, "while"),
+            new_token!(With, "with"),
             new_token!(EOF, ""),
         ];
 
+        // Spans are checked separately, so compare what was scanned rather than where
         for (idx, t) in tokens.iter().enumerate() {
             let e = &expected_tokens[idx];
-            assert_eq!(t, e);
+            assert_eq!(
+                (&t.token_type, &t.lexeme, &t.literal, t.line),
+                (&e.token_type, &e.lexeme, &e.literal, e.line)
+            );
         }
     }
+
+    #[test]
+    fn errors() {
+        let result = Scanner::new("print 1; @ \"unterminated".to_string()).scan_tokens();
+        assert_eq!(Err(LoxError::Static), result);
+    }
+
+    #[test]
+    fn ranges() {
+        let tokens = Scanner::new("1..5 1.5..=2 x.y".to_string())
+            .scan_tokens()
+            .unwrap();
+        let types: Vec<TokenType> = tokens.into_iter().map(|t| t.token_type).collect();
+        assert_eq!(
+            vec![
+                TokenType::Number,
+                TokenType::DotDot,
+                TokenType::Number,
+                TokenType::Number,
+                TokenType::DotDotEqual,
+                TokenType::Number,
+                TokenType::Identifier,
+                TokenType::Dot,
+                TokenType::Identifier,
+                TokenType::EOF,
+            ],
+            types
+        );
+    }
+
+    #[test]
+    fn updates() {
+        let tokens = Scanner::new("+= -= *= /= %= ++ -- + - - -".to_string())
+            .scan_tokens()
+            .unwrap();
+        let types: Vec<TokenType> = tokens.into_iter().map(|t| t.token_type).collect();
+        assert_eq!(
+            vec![
+                TokenType::PlusEqual,
+                TokenType::MinusEqual,
+                TokenType::StarEqual,
+                TokenType::SlashEqual,
+                TokenType::PercentEqual,
+                TokenType::PlusPlus,
+                TokenType::MinusMinus,
+                TokenType::Plus,
+                TokenType::Minus,
+                TokenType::Minus,
+                TokenType::Minus,
+                TokenType::EOF,
+            ],
+            types
+        );
+    }
+
+    #[test]
+    fn numbers() {
+        let tokens = Scanner::new("7 7.0 9007199254740993".to_string())
+            .scan_tokens()
+            .unwrap();
+        let literals: Vec<TokenLiteral> = tokens.into_iter().map(|t| t.literal).collect();
+        assert!(matches!(literals[0], TokenLiteral::Num(Number::Int(7))));
+        assert!(matches!(literals[1], TokenLiteral::Num(Number::Float(f)) if f == 7.0));
+        // Too big to be a float exactly
+        assert!(matches!(
+            literals[2],
+            TokenLiteral::Num(Number::Int(9_007_199_254_740_993))
+        ));
+
+        let tokens = Scanner::new("100000000000000000000".to_string())
+            .scan_tokens()
+            .unwrap();
+        let TokenLiteral::Num(Number::Big(n)) = &tokens[0].literal else {
+            panic!("expected a big integer");
+        };
+        assert_eq!("100000000000000000000", n.to_string());
+    }
+
+    #[test]
+    fn spans() {
+        let source = "var greeting = \"hi\";\nprint greeting;";
+        let mut scanner = Scanner::new(source.to_string());
+        let tokens = scanner.scan_tokens().unwrap();
+
+        let slices: Vec<&str> = tokens.iter().map(|t| t.span.slice(source)).collect();
+        assert_eq!(
+            vec![
+                "var", "greeting", "=", "\"hi\"", ";", "print", "greeting", ";", ""
+            ],
+            slices
+        );
+        assert_eq!(2, tokens[5].span.line);
+    }
+
+    #[test]
+    fn non_ascii() {
+        let source = "print \"héllo → wörld\"; // ünïcode\nprint 1;";
+        let tokens = Scanner::new(source.to_string()).scan_tokens().unwrap();
+
+        let slices: Vec<&str> = tokens.iter().map(|t| t.span.slice(source)).collect();
+        assert_eq!(
+            vec!["print", "\"héllo → wörld\"", ";", "print", "1", ";", ""],
+            slices
+        );
+        assert_eq!("héllo → wörld", tokens[1].lexeme);
+    }
 }
@@ -0,0 +1,50 @@
+use std::fmt::Display;
+
+/// A region of source text, as byte offsets into the scanned source plus the line it starts on
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize, line: usize) -> Self {
+        Self { start, end, line }
+    }
+
+    /// Returns a span running from the start of `self` to the end of `other`
+    pub fn to(self, other: Span) -> Span {
+        Span {
+            start: self.start.min(other.start),
+            end: self.end.max(other.end),
+            line: self.line.min(other.line),
+        }
+    }
+
+    /// The source text covered by the span
+    #[allow(dead_code)] // Only used by tests to check spans for now
+    pub fn slice(self, source: &str) -> &str {
+        &source[self.start..self.end]
+    }
+}
+
+impl Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}..{}", self.start, self.end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merge() {
+        let source = "1 + 2";
+        let merged = Span::new(0, 1, 1).to(Span::new(4, 5, 1));
+
+        assert_eq!(Span::new(0, 5, 1), merged);
+        assert_eq!("1 + 2", merged.slice(source));
+    }
+}
@@ -0,0 +1,90 @@
+use crate::ast::{ExprId, StmtId};
+use crate::scanner::Token;
+
+pub enum Statement {
+    Expression(ExprId),
+    Print(Print),
+    Var(Var),
+    Block(Vec<StmtId>),
+    If(If),
+    While(While),
+    ForIn(ForIn),
+    Break,
+    Continue,
+    Function(Function),
+    Return(Return),
+    Class(Class),
+    Trait(Trait),
+}
+
+/// `print value;`
+pub struct Print {
+    pub keyword: Token,
+    pub value: ExprId,
+}
+
+/// `var name = initializer;`
+pub struct Var {
+    pub name: Token,
+    pub initializer: Option<ExprId>,
+}
+
+/// `if (condition) then_branch else else_branch`
+pub struct If {
+    pub condition: ExprId,
+    pub then_branch: StmtId,
+    pub else_branch: Option<StmtId>,
+}
+
+/// `while (condition) body`. `for` loops are desugared into this, with their increment clause
+/// kept separate so that `continue` still runs it.
+pub struct While {
+    pub condition: ExprId,
+    pub body: StmtId,
+    pub increment: Option<ExprId>,
+}
+
+/// `for (name in iterable) body`
+pub struct ForIn {
+    pub name: Token,
+    /// The `in`, which errors while iterating are reported at
+    pub keyword: Token,
+    pub iterable: ExprId,
+    pub body: StmtId,
+}
+
+/// `fun name(params) { body }`, or a method `name(params) { body }`
+pub struct Function {
+    pub name: Token,
+    pub params: Vec<Token>,
+    pub body: Vec<StmtId>,
+    /// Whether this is a method declared without a parameter list, which runs as soon as it's
+    /// accessed
+    pub getter: bool,
+}
+
+/// `return value;`
+pub struct Return {
+    pub keyword: Token,
+    pub value: Option<ExprId>,
+}
+
+/// `class name < superclass with traits { methods class class_methods }`
+pub struct Class {
+    pub name: Token,
+    /// An `Expression::Variable` naming the superclass
+    pub superclass: Option<ExprId>,
+    /// `Expression::Variable`s naming the traits mixed in
+    pub traits: Vec<ExprId>,
+    /// `Statement::Function`s, one per method
+    pub methods: Vec<StmtId>,
+    /// `Statement::Function`s for the methods declared with `class`, called on the class itself
+    pub class_methods: Vec<StmtId>,
+}
+
+/// `trait name { methods }`
+pub struct Trait {
+    pub name: Token,
+    /// `Statement::Function`s, one per method
+    pub methods: Vec<StmtId>,
+}
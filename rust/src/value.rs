@@ -0,0 +1,228 @@
+use crate::callable::Callable;
+use crate::class::{LoxClass, LoxInstance, LoxTrait};
+use crate::expression::Literal;
+use crate::map::Map;
+use crate::number::Number;
+use crate::range::Range;
+use std::{cell::RefCell, fmt::Display, ptr, rc::Rc};
+
+/// A value produced at runtime
+#[derive(Clone, Debug)]
+pub enum Value {
+    Nil,
+    Boolean(bool),
+    Number(Number),
+    String(String),
+    Callable(Rc<dyn Callable>),
+    Class(Rc<LoxClass>),
+    Instance(Rc<RefCell<LoxInstance>>),
+    Trait(Rc<LoxTrait>),
+    List(Rc<RefCell<Vec<Value>>>),
+    Map(Rc<RefCell<Map>>),
+    Range(Range),
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        equal(self, other, &mut vec![])
+    }
+}
+
+/// Lists or maps that are being compared further up, and so are equal unless something else
+/// about them differs
+type Comparing = Vec<(*const (), *const ())>;
+
+fn equal(left: &Value, right: &Value, comparing: &mut Comparing) -> bool {
+    match (left, right) {
+        (Value::Nil, Value::Nil) => true,
+        (Value::Boolean(l), Value::Boolean(r)) => l == r,
+        (Value::Number(l), Value::Number(r)) => l == r,
+        (Value::String(l), Value::String(r)) => l == r,
+        // Functions are only equal to themselves
+        (Value::Callable(l), Value::Callable(r)) => ptr::addr_eq(Rc::as_ptr(l), Rc::as_ptr(r)),
+        (Value::Class(l), Value::Class(r)) => Rc::ptr_eq(l, r),
+        (Value::Instance(l), Value::Instance(r)) => Rc::ptr_eq(l, r),
+        (Value::Trait(l), Value::Trait(r)) => Rc::ptr_eq(l, r),
+        // Lists are equal when their elements are
+        (Value::List(l), Value::List(r)) => equal_shared(l, r, comparing, |comparing| {
+            let (l, r) = (l.borrow(), r.borrow());
+            l.len() == r.len() && l.iter().zip(r.iter()).all(|(l, r)| equal(l, r, comparing))
+        }),
+        // Maps are equal when they have the same entries, whatever order they were inserted in
+        (Value::Map(l), Value::Map(r)) => equal_shared(l, r, comparing, |comparing| {
+            let (l, r) = (l.borrow(), r.borrow());
+            l.len() == r.len()
+                && l.entries().all(|(key, value)| {
+                    matches!(r.get(key), Ok(Some(other)) if equal(value, other, comparing))
+                })
+        }),
+        (Value::Range(l), Value::Range(r)) => l == r,
+        _ => false,
+    }
+}
+
+/// Compares two lists or two maps with `compare`, short-circuiting when they're the same one
+/// or when they're already being compared, which means they contain themselves
+fn equal_shared<T>(
+    left: &Rc<RefCell<T>>,
+    right: &Rc<RefCell<T>>,
+    comparing: &mut Comparing,
+    compare: impl FnOnce(&mut Comparing) -> bool,
+) -> bool {
+    let pair = (
+        Rc::as_ptr(left) as *const (),
+        Rc::as_ptr(right) as *const (),
+    );
+    if Rc::ptr_eq(left, right) || comparing.contains(&pair) {
+        return true;
+    }
+
+    comparing.push(pair);
+    let equal = compare(comparing);
+    comparing.pop();
+    equal
+}
+
+impl Value {
+    /// Lox follows Ruby's rule: `nil` and `false` are falsey and everything else is truthy
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Value::Nil | Value::Boolean(false))
+    }
+
+    /// The name of the value's type, as returned by `type()`
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Nil => "nil",
+            Value::Boolean(_) => "boolean",
+            Value::Number(_) => "number",
+            Value::String(_) => "string",
+            Value::Callable(_) => "function",
+            Value::Class(_) => "class",
+            Value::Instance(_) => "instance",
+            Value::Trait(_) => "trait",
+            Value::List(_) => "list",
+            Value::Map(_) => "map",
+            Value::Range(_) => "range",
+        }
+    }
+}
+
+impl From<&Literal> for Value {
+    fn from(literal: &Literal) -> Self {
+        match literal {
+            Literal::Nil => Value::Nil,
+            Literal::Boolean(b) => Value::Boolean(*b),
+            Literal::Number(n) => Value::Number(n.clone()),
+            Literal::String(s) => Value::String(s.to_owned()),
+        }
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write_value(f, self, &mut vec![])
+    }
+}
+
+/// Writes `value`, where `enclosing` holds the lists and maps it's inside of. A list or map
+/// that contains itself is written as `[...]` or `{...}` where it repeats.
+fn write_value(
+    f: &mut std::fmt::Formatter<'_>,
+    value: &Value,
+    enclosing: &mut Vec<*const ()>,
+) -> std::fmt::Result {
+    match value {
+        Value::Nil => write!(f, "nil"),
+        Value::Boolean(b) => write!(f, "{b}"),
+        Value::Number(n) => write!(f, "{n}"),
+        Value::String(s) => write!(f, "{s}"),
+        Value::Callable(c) => write!(f, "<fn {}>", c.name()),
+        Value::Class(c) => write!(f, "{}", c.name),
+        Value::Instance(i) => write!(f, "{} instance", i.borrow().class.name),
+        Value::Trait(t) => write!(f, "{}", t.name),
+        Value::List(list) => {
+            let pointer = Rc::as_ptr(list) as *const ();
+            if enclosing.contains(&pointer) {
+                return write!(f, "[...]");
+            }
+
+            enclosing.push(pointer);
+            write!(f, "[")?;
+            for (i, element) in list.borrow().iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write_value(f, element, enclosing)?;
+            }
+            enclosing.pop();
+            write!(f, "]")
+        }
+        Value::Map(map) => {
+            let pointer = Rc::as_ptr(map) as *const ();
+            if enclosing.contains(&pointer) {
+                return write!(f, "{{...}}");
+            }
+
+            enclosing.push(pointer);
+            write!(f, "{{")?;
+            for (i, (key, value)) in map.borrow().entries().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{key}: ")?;
+                write_value(f, value, enclosing)?;
+            }
+            enclosing.pop();
+            write!(f, "}}")
+        }
+        Value::Range(range) => write!(f, "{range}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::tests::run;
+
+    #[test]
+    fn truthiness() {
+        assert!(!Value::Nil.is_truthy());
+        assert!(!Value::Boolean(false).is_truthy());
+        assert!(Value::Boolean(true).is_truthy());
+        assert!(Value::Number(Number::Int(0)).is_truthy());
+        assert!(Value::String(String::new()).is_truthy());
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!("3", Value::Number(Number::Int(3)).to_string());
+        assert_eq!("3.0", Value::Number(Number::Float(3.0)).to_string());
+        assert_eq!("nil", Value::Nil.to_string());
+        assert_eq!("hi", Value::String("hi".to_string()).to_string());
+    }
+
+    #[test]
+    fn cycles() {
+        let source = "
+            var xs = [1];
+            xs.push(xs);
+            print xs;
+            var m = {\"xs\": xs};
+            m[\"m\"] = m;
+            print m;
+            var ys = [1];
+            ys.push(ys);
+            print xs == ys;
+            ys[0] = 2;
+            print xs == ys;
+            var shared = [];
+            print [shared, shared];
+        ";
+        let (output, err) = run(source);
+        assert_eq!(
+            "[1, [...]]\n{xs: [1, [...]], m: {...}}\ntrue\nfalse\n[[], []]\n",
+            output
+        );
+        assert!(err.is_none());
+    }
+}